@@ -5,14 +5,10 @@ extern crate uuid;
 use gdax_client::{NewOrder, PrivateClient, Side, SizeOrFunds};
 use uuid::Uuid;
 
-const CB_KEY: &'static str = env!("CB_KEY");
-const CB_SECRET: &'static str = env!("CB_SECRET");
-const CB_PASSPHRASE: &'static str = env!("CB_PASSPHRASE");
-
 fn main() {
     env_logger::init().unwrap();
 
-    let private_client = PrivateClient::new(CB_KEY, CB_SECRET, CB_PASSPHRASE);
+    let private_client = PrivateClient::from_env().unwrap();
 
     if let Ok(accounts) = private_client.get_accounts() {
         println!("Accounts: {:?}", accounts);
@@ -27,19 +23,24 @@ fn main() {
         }
     }
 
-    //let order = NewOrder::limit(Side::Buy, "BTC-CAD", 1.01, 1.01);
-    //println!("Posting limit order: {:?} {:?}", order, private_client.post_order(&order));
+    //let order = NewOrder::limit(Side::Buy, "BTC-CAD", 1.01, 1.01).unwrap();
+    //println!("Posting limit order: {} {:?}", order, private_client.post_order(&order));
 
-    //let order = NewOrder::market(Side::Buy, "BTC-CAD", SizeOrFunds::Funds(10000.));
-    //println!("Posting market order: {:?} {:?}", order, private_client.post_order(&order));
+    //let order = NewOrder::market(Side::Buy, "BTC-CAD", SizeOrFunds::Funds(10000.)).unwrap();
+    //println!("Posting market order: {} {:?}", order, private_client.post_order(&order));
 
-    //let order = NewOrder::market(Side::Buy, "BTC-CAD", SizeOrFunds::Size(1000.));
-    //println!("Posting market order: {:?} {:?}", order, private_client.post_order(&order));
+    //let order = NewOrder::market(Side::Buy, "BTC-CAD", SizeOrFunds::Size(1000.)).unwrap();
+    //println!("Posting market order: {} {:?}", order, private_client.post_order(&order));
 
-    //let order = NewOrder::stop(Side::Buy, "BTC-CAD", SizeOrFunds::Size(1.01), 1.01);
-    //println!("Posting stop order: {:?} {:?}", order, private_client.post_order(&order));
+    //let order = NewOrder::stop(Side::Buy, "BTC-CAD", SizeOrFunds::Size(1.01), 1.01).unwrap();
+    //println!("Posting stop order: {} {:?}", order, private_client.post_order(&order));
 
-    println!("All Open Orders: {:?}", private_client.get_orders());
+    if let Ok(orders) = private_client.get_orders() {
+        println!("All Open Orders:");
+        for order in orders {
+            println!("    {}", order);
+        }
+    }
 
     //println!("Bogus order: {:?}", private_client.get_order(Uuid::new_v4()));
 
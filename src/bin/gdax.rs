@@ -0,0 +1,121 @@
+extern crate gdax_client;
+
+use std::env;
+use std::process;
+
+use gdax_client::{Error, NewOrder, PrivateClient, PublicClient, Side, SizeOrFunds};
+
+const USAGE: &'static str = "\
+Usage:
+    gdax ticker <product>
+    gdax book <product> <1|2|3>
+    gdax balance
+    gdax orders
+    gdax cancel-all [product]
+    gdax order limit <buy|sell> <product> <size> <price>
+    gdax order market <buy|sell> <product> <size|funds> <amount>
+
+Private commands read credentials from CB_KEY, CB_SECRET and CB_PASSPHRASE.";
+
+fn usage() -> ! {
+    println!("{}", USAGE);
+    process::exit(1);
+}
+
+fn fail(err: Error) -> ! {
+    println!("error: {:?}", err);
+    process::exit(1);
+}
+
+fn private_client() -> PrivateClient {
+    match PrivateClient::from_env() {
+        Ok(client) => client,
+        Err(err) => fail(err)
+    }
+}
+
+fn parse_side(side: &str) -> Side {
+    match &*side.to_lowercase() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        _ => usage()
+    }
+}
+
+fn parse_amount(amount: &str) -> f64 {
+    match amount.parse() {
+        Ok(amount) => amount,
+        Err(_) => usage()
+    }
+}
+
+fn parse_order(args: &[String]) -> Result<NewOrder, Error> {
+    match (args.get(0).map(|s| &**s), args.len()) {
+        (Some("limit"), 5) => NewOrder::limit(parse_side(&args[1]),
+                                              &args[2],
+                                              parse_amount(&args[3]),
+                                              parse_amount(&args[4])),
+        (Some("market"), 5) => {
+            let amount = parse_amount(&args[4]);
+            let size_or_funds = match &*args[3] {
+                "size" => SizeOrFunds::Size(amount),
+                "funds" => SizeOrFunds::Funds(amount),
+                _ => usage()
+            };
+            NewOrder::market(parse_side(&args[1]), &args[2], size_or_funds)
+        }
+        _ => usage()
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let command = args.get(0).map(|s| &**s);
+
+    let result = match (command, args.len()) {
+        (Some("ticker"), 2) => {
+            PublicClient::new().get_product_ticker(&args[1])
+                               .map(|tick| println!("{:?}", tick))
+        }
+        (Some("book"), 3) => {
+            let client = PublicClient::new();
+            match &*args[2] {
+                "1" => client.get_best_order(&args[1]).map(|book| println!("{:?}", book)),
+                "2" => client.get_top50_orders(&args[1]).map(|book| println!("{:?}", book)),
+                "3" => client.get_full_book(&args[1]).map(|book| println!("{:?}", book)),
+                _ => usage()
+            }
+        }
+        (Some("balance"), 1) => {
+            private_client().get_accounts().map(|accounts| {
+                for account in accounts {
+                    println!("{}\tbalance: {}\tavailable: {}\thold: {}",
+                             account.currency,
+                             account.balance,
+                             account.available,
+                             account.hold);
+                }
+            })
+        }
+        (Some("orders"), 1) => {
+            private_client().get_orders().map(|orders| {
+                for order in orders {
+                    println!("{} [{}]", order, order.id);
+                }
+            })
+        }
+        (Some("cancel-all"), 1) | (Some("cancel-all"), 2) => {
+            private_client().cancel_all_orders(args.get(1).map(|s| &**s))
+                            .map(|ids| println!("Cancelled: {:?}", ids))
+        }
+        (Some("order"), _) => {
+            parse_order(&args[1..]).and_then(|order| private_client().post_order(&order))
+                                   .map(|id| println!("Posted order {}", id))
+        }
+        _ => usage()
+    };
+
+    if let Err(err) = result {
+        fail(err);
+    }
+}
@@ -0,0 +1,113 @@
+use hyper::client::Client as HttpClient;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Error;
+use super::{PrivateClient, PublicClient};
+use super::signing::check_secret;
+
+const DEFAULT_API_URL: &'static str = "https://api.gdax.com";
+const DEFAULT_USER_AGENT: &'static str = "rust-gdax-client/0.1.0";
+
+// The settings a built client sends its requests with. Shared between
+// a private client and the public client it wraps so they can't drift.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub base_url: String,
+    pub user_agent: String,
+    pub rate_limit: bool,
+    pub retries: u32,
+    pub connection_close: bool
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            base_url: DEFAULT_API_URL.to_owned(),
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+            rate_limit: true,
+            retries: 0,
+            connection_close: false
+        }
+    }
+}
+
+// Configures and builds either client. Both clients built from the same
+// builder behave the same way, and a private client's public endpoints
+// go through the same connection pool as its private ones.
+#[derive(Clone, Debug)]
+pub struct ClientBuilder {
+    config: Config,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>
+}
+
+impl ClientBuilder {
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            config: Config::new(),
+            read_timeout: None,
+            write_timeout: None
+        }
+    }
+
+    // e.g. the sandbox, "https://api-public.sandbox.gdax.com"
+    pub fn with_base_url(mut self, base_url: &str) -> ClientBuilder {
+        self.config.base_url = base_url.trim_right_matches('/').to_owned();
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> ClientBuilder {
+        self.config.user_agent = user_agent.to_owned();
+        self
+    }
+
+    pub fn with_read_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_write_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    // Whether to hold requests back when GDAX reports the rate limit
+    // is nearly used up. On by default.
+    pub fn with_rate_limit(mut self, enabled: bool) -> ClientBuilder {
+        self.config.rate_limit = enabled;
+        self
+    }
+
+    // How many times a GET that fails in transit is retried. Requests
+    // that change anything are never retried, see `with_idempotent_orders`
+    // on the private client for retrying orders safely.
+    pub fn with_retries(mut self, retries: u32) -> ClientBuilder {
+        self.config.retries = retries;
+        self
+    }
+
+    // See `with_connection_close` on the clients
+    pub fn with_connection_close(mut self) -> ClientBuilder {
+        self.config.connection_close = true;
+        self
+    }
+
+    fn http_client(&self) -> Arc<HttpClient> {
+        let mut http_client = HttpClient::new();
+        http_client.set_read_timeout(self.read_timeout);
+        http_client.set_write_timeout(self.write_timeout);
+        Arc::new(http_client)
+    }
+
+    pub fn build_public(&self) -> PublicClient {
+        PublicClient::from_config(self.config.clone(), self.http_client())
+    }
+
+    pub fn build_private(&self, key: &str, secret: &str, passphrase: &str)
+        -> Result<PrivateClient, Error> {
+
+        check_secret(secret)?;
+        Ok(PrivateClient::from_config(key, secret, passphrase, self.config.clone(), self.http_client()))
+    }
+}
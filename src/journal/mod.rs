@@ -0,0 +1,169 @@
+use chrono::{DateTime, UTC};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use serde_json::ser;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::Error;
+use super::private::{Fill, NewOrder, OrderId};
+
+// Receives every order the private client posts or cancels and every
+// fill it sees, along with how each request turned out. Set one with
+// `PrivateClient::with_journal`. An error writing to the journal is
+// logged but never fails the order operation itself.
+pub trait OrderJournal: Send + Sync {
+    fn order_posted(&self, order: &NewOrder, result: &Result<OrderId, Error>) -> Result<(), Error>;
+    fn order_cancelled(&self, order_id: OrderId, result: &Result<OrderId, Error>) -> Result<(), Error>;
+    fn fills_observed(&self, fills: &[Fill]) -> Result<(), Error>;
+
+    // A cancel of every open order, or every one on `product_id`. By
+    // default each order it cancelled is recorded as a single cancel,
+    // so a failed mass cancel leaves nothing behind.
+    fn all_orders_cancelled(&self, product_id: Option<&str>, result: &Result<Vec<OrderId>, Error>)
+        -> Result<(), Error> {
+
+        if let Ok(ref order_ids) = *result {
+            for &order_id in order_ids {
+                self.order_cancelled(order_id, &Ok(order_id))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Makes sure everything recorded so far is durably stored.
+    // Journals that don't buffer have nothing to do.
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    time: DateTime<UTC>,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<&'a NewOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_id: Option<OrderId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_ids: Option<&'a [OrderId]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fill: Option<&'a Fill>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    // SHA-256 of the previous line, so removing or editing
+    // a line breaks the chain from that point on
+    prev: String
+}
+
+// Appends one JSON object per line to a file. Each line carries the
+// hash of the line before it, making after-the-fact edits detectable.
+pub struct FileJournal {
+    state: Mutex<(File, String)>
+}
+
+impl FileJournal {
+    // Opens `path` for appending, creating it if needed and
+    // picking up the hash chain where an existing file left off
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileJournal, Error> {
+        let mut contents = String::new();
+        if path.as_ref().exists() {
+            File::open(path.as_ref())?.read_to_string(&mut contents)?;
+        }
+        let prev = contents.lines().filter(|line| !line.is_empty()).last().map_or(String::new(), hash);
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileJournal {
+            state: Mutex::new((file, prev))
+        })
+    }
+
+    fn write(&self, event: &'static str,
+             order: Option<&NewOrder>,
+             order_id: Option<OrderId>,
+             fill: Option<&Fill>,
+             error: Option<&Error>)
+        -> Result<(), Error> {
+
+        self.write_entry(Entry {
+            time: UTC::now(),
+            event: event,
+            order: order,
+            order_id: order_id,
+            order_ids: None,
+            product_id: None,
+            fill: fill,
+            error: error.map(|err| format!("{:?}", err)),
+            prev: String::new()
+        })
+    }
+
+    // Fills in `prev` and appends the entry
+    fn write_entry(&self, mut entry: Entry) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        entry.prev = state.1.clone();
+        let line = ser::to_string(&entry)?;
+
+            error: error.map(|err| format!("{:?}", err)),
+            prev: state.1.clone()
+        })?;
+
+        writeln!(state.0, "{}", line)?;
+        state.0.flush()?;
+        state.1 = hash(&line);
+        Ok(())
+    }
+}
+
+impl OrderJournal for FileJournal {
+    fn order_posted(&self, order: &NewOrder, result: &Result<OrderId, Error>) -> Result<(), Error> {
+        self.write("order_posted", Some(order), result.as_ref().ok().cloned(), None, result.as_ref().err())
+    }
+
+    fn order_cancelled(&self, order_id: OrderId, result: &Result<OrderId, Error>) -> Result<(), Error> {
+        self.write("order_cancelled", None, Some(order_id), None, result.as_ref().err())
+    }
+
+    fn fills_observed(&self, fills: &[Fill]) -> Result<(), Error> {
+        for fill in fills {
+            self.write("fill", None, Some(fill.order_id), Some(fill), None)?;
+        }
+        Ok(())
+    }
+
+    fn all_orders_cancelled(&self, product_id: Option<&str>, result: &Result<Vec<OrderId>, Error>)
+        -> Result<(), Error> {
+
+        self.write_entry(Entry {
+            time: UTC::now(),
+            event: "all_orders_cancelled",
+            order: None,
+            order_id: None,
+            order_ids: result.as_ref().ok().map(|order_ids| &order_ids[..]),
+            product_id: product_id,
+            fill: None,
+            error: result.as_ref().err().map(|err| format!("{:?}", err)),
+            prev: String::new()
+        })
+    }
+
+    // Every line is already flushed to the OS as it's written,
+    // this also waits for it to reach the disk
+    fn flush(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.0.flush()?;
+        state.0.sync_data()?;
+        Ok(())
+    }
+}
+
+fn hash(line: &str) -> String {
+    let mut sha = Sha256::new();
+    sha.input_str(line);
+    sha.result_str()
+}
@@ -5,37 +5,109 @@ extern crate base64;
 extern crate chrono;
 extern crate crypto;
 extern crate hyper;
+#[macro_use]
+extern crate log;
 extern crate serde;
 extern crate serde_json;
 extern crate time;
 extern crate uuid;
 
+use chrono::{DateTime, UTC};
+use hyper::client::Response;
+use hyper::header::{ContentType, Headers};
+use hyper::mime::{Mime, SubLevel, TopLevel};
+use std::cmp;
+use std::collections::VecDeque;
 use std::fmt;
+use std::io::Read;
+use std::panic;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 pub mod public;
 pub mod private;
+pub mod journal;
+pub mod market;
+pub mod pnl;
+pub mod prelude;
+mod builder;
+mod numeric;
+mod rate_limit;
+mod signing;
 
+#[cfg(feature = "test-util")]
+pub mod test_util {
+    pub use signing::{sign, signing_vectors, SigningVector, Timestamper, EXAMPLE_SECRET};
+}
+
+pub use builder::ClientBuilder;
 pub use public::Client as PublicClient;
 pub use private::Client as PrivateClient;
 
-pub use private::NewOrder;
+pub use private::{AccountsExt, LedgerExt, NewOrder, OrderError, OrderLike, OrderStatus};
 pub use private::SizeOrFunds::{self, Funds, Size};
+pub use rate_limit::RateLimitStatus;
 
 #[derive(Debug, Deserialize)]
 pub struct ApiError {
-    message: String
+    message: String,
+    #[serde(default)]
+    reason: Option<String>
+}
+
+impl ApiError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    // Some errors carry a secondary field with more detail
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_ref().map(|r| &**r)
+    }
+
+    // GDAX answers a lookup of something that doesn't exist with
+    // either "NotFound" or a message like "order not found"
+    pub fn is_not_found(&self) -> bool {
+        let message = self.message.to_lowercase();
+        message == "notfound" || message.contains("not found")
+    }
+
+    // Cancelling an order that already filled or was cancelled
+    pub fn is_order_done(&self) -> bool {
+        self.message.to_lowercase().contains("already done")
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
     Api(ApiError),
+    // GDAX couldn't be reached at all: DNS, a refused or dropped
+    // connection, a timeout or a TLS failure
+    Connection(String),
     Http(hyper::Error),
+    InsufficientFunds { currency: String, required: f64, available: f64 },
+    InvalidArgument(String),
+    InvalidOrder(private::OrderError),
+    InvalidOrderStatus(private::OrderStatus),
+    InvalidProductId(String),
+    InvalidResponse(String),
     InvalidSecretKey,
+    Io(std::io::Error),
     Json(serde_json::Error),
+    MissingCredential(&'static str),
+    // An order with this `client_oid` failed in transit and lookups never
+    // found it, which doesn't prove it didn't land. Check before resending.
+    OrderOutcomeUnknown(uuid::Uuid),
+    // A post only order was rejected because it would have taken
+    // liquidity, requote at a more passive price
+    PostOnlyWouldCross,
+    ServiceUnavailable(String),
+    Timeout,
 }
 
-impl std::convert::From<base64::Base64Error> for Error {
-    fn from(_: base64::Base64Error) -> Error {
+impl std::convert::From<base64::DecodeError> for Error {
+    fn from(_: base64::DecodeError) -> Error {
         // Only time we get a base64 error is when decoding secret key
         Error::InvalidSecretKey
     }
@@ -43,7 +115,17 @@ impl std::convert::From<base64::Base64Error> for Error {
 
 impl std::convert::From<hyper::Error> for Error {
     fn from(err: hyper::Error) -> Error {
-        Error::Http(err)
+        match err {
+            hyper::Error::Io(err) => Error::Connection(err.to_string()),
+            hyper::Error::Ssl(err) => Error::Connection(err.to_string()),
+            err => Error::Http(err)
+        }
+    }
+}
+
+impl std::convert::From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
     }
 }
 
@@ -53,6 +135,350 @@ impl std::convert::From<serde_json::Error> for Error {
     }
 }
 
+// Turns an unsuccessful response into an `Error`. GDAX normally replies
+// with a JSON `ApiError` but during maintenance or Cloudflare outages the
+// body is an HTML page, which we pass along rather than failing to parse.
+fn api_error(res: &mut Response) -> Error {
+    let mut body = String::new();
+    if let Err(err) = res.read_to_string(&mut body) {
+        return Error::Io(err);
+    }
+
+    let is_json = match res.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::Json, _))) | None => true,
+        Some(_) => false
+    };
+
+    if !is_json || body.trim_left().starts_with('<') {
+        return Error::ServiceUnavailable(body.chars().take(200).collect());
+    }
+
+    match serde_json::from_str(&body) {
+        Ok(err) => Error::Api(err),
+        Err(err) => Error::Json(err)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum CursorDirection {
+    Before,
+    After
+}
+
+// A position in a paginated list. `after` pages towards older items
+// and `before` towards newer ones, mirroring GDAX's query parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cursor {
+    direction: CursorDirection,
+    id: String
+}
+
+impl Cursor {
+    pub fn before(id: String) -> Cursor {
+        Cursor {
+            direction: CursorDirection::Before,
+            id: id
+        }
+    }
+
+    pub fn after(id: String) -> Cursor {
+        Cursor {
+            direction: CursorDirection::After,
+            id: id
+        }
+    }
+
+    fn query(&self) -> String {
+        match self.direction {
+            CursorDirection::Before => format!("before={}", self.id),
+            CursorDirection::After => format!("after={}", self.id)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Cursor for the page of older items
+    pub next: Option<Cursor>,
+    // Cursor for the page of newer items
+    pub prev: Option<Cursor>
+}
+
+impl<T> Page<T> {
+    fn from_headers(items: Vec<T>, headers: &Headers) -> Page<T> {
+        let header = |name: &str| {
+            headers.get_raw(name)
+                   .and_then(|values| values.first())
+                   .and_then(|value| String::from_utf8(value.clone()).ok())
+        };
+
+        Page {
+            items: items,
+            next: header("CB-AFTER").map(Cursor::after),
+            prev: header("CB-BEFORE").map(Cursor::before)
+        }
+    }
+}
+
+// Follows a paginated list from its newest page towards older ones.
+// `next_page` hands over a page at a time, so the caller can stop as
+// soon as it has gone back far enough, while iterating yields the items
+// one by one, fetching pages as they're needed. Either way it stops at
+// the first error.
+pub struct PaginatedStream<'a, T> {
+    fetch: Box<FnMut(Option<&Cursor>) -> Result<Page<T>, Error> + 'a>,
+    cursor: Option<Cursor>,
+    done: bool,
+    buffered: VecDeque<T>
+}
+
+impl<'a, T> PaginatedStream<'a, T> {
+    // `fetch` gets the page at a cursor, or the first page given `None`
+    pub fn new<F>(fetch: F) -> PaginatedStream<'a, T>
+        where F: FnMut(Option<&Cursor>) -> Result<Page<T>, Error> + 'a
+    {
+        PaginatedStream {
+            fetch: Box::new(fetch),
+            cursor: None,
+            done: false,
+            buffered: VecDeque::new()
+        }
+    }
+
+    // `None` once there are no pages left
+    pub fn next_page(&mut self) -> Option<Result<Vec<T>, Error>> {
+        if self.done {
+            return None;
+        }
+
+        let page = match (self.fetch)(self.cursor.as_ref()) {
+            Ok(page) => page,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+
+        if page.items.is_empty() {
+            self.done = true;
+            return None;
+        }
+
+        self.done = page.next.is_none();
+        self.cursor = page.next;
+        Some(Ok(page.items))
+    }
+}
+
+impl<'a, T> Iterator for PaginatedStream<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        loop {
+            if let Some(item) = self.buffered.pop_front() {
+                return Some(Ok(item));
+            }
+
+            match self.next_page() {
+                Some(Ok(items)) => self.buffered.extend(items),
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None
+            }
+        }
+    }
+}
+
+// Builds `path?query`, adding the cursor's parameter when given
+fn with_query(path: &str, mut query: Vec<String>, cursor: Option<&Cursor>) -> String {
+    if let Some(cursor) = cursor {
+        query.push(cursor.query());
+    }
+
+    if query.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{}?{}", path, query.join("&"))
+    }
+}
+
+// How a batch operation reacts to one of its requests failing
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FailureMode {
+    // Carry on and return every result
+    Collect,
+    // Stop at the first error, which is then the last result returned
+    FailFast
+}
+
+impl FailureMode {
+    fn run<I, T, F>(self, items: I, mut f: F) -> Vec<Result<T, Error>>
+        where I: IntoIterator,
+              F: FnMut(I::Item) -> Result<T, Error>
+    {
+        let mut results = Vec::new();
+        for item in items {
+            let result = f(item);
+            let failed = result.is_err();
+            results.push(result);
+
+            if failed && self == FailureMode::FailFast {
+                break;
+            }
+        }
+        results
+    }
+}
+
+// Most requests a fan-out keeps in flight at once. The rate limiter
+// only slows down once GDAX reports the budget running low, so without
+// a bound a large fan-out would go out as a single burst.
+const MAX_CONCURRENT_REQUESTS: usize = 3;
+
+// Calls `f` with a clone of `context` for each of `items` on a small
+// pool of threads, at most `MAX_CONCURRENT_REQUESTS` at a time. The
+// results come back in the same order as `items`.
+fn fan_out<C, T, R, F>(context: &C, items: Vec<T>, f: F) -> Vec<R>
+    where C: Clone + Send + 'static,
+          T: Send + 'static,
+          R: Send + 'static,
+          F: Fn(&C, T) -> R + Send + Sync + 'static
+{
+    let workers = cmp::min(MAX_CONCURRENT_REQUESTS, items.len());
+    let queue = Arc::new(Mutex::new(items.into_iter().enumerate()));
+    let f = Arc::new(f);
+
+    let handles = (0..workers).map(|_| {
+        let context = context.clone();
+        let queue = queue.clone();
+        let f = f.clone();
+        thread::spawn(move || {
+            let mut results = Vec::new();
+            loop {
+                let next = queue.lock().unwrap().next();
+                match next {
+                    Some((index, item)) => results.push((index, f(&context, item))),
+                    None => return results
+                }
+            }
+        })
+    }).collect::<Vec<_>>();
+
+    let mut results = Vec::new();
+    for handle in handles {
+        match handle.join() {
+            Ok(done) => results.extend(done),
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+
+    results.sort_by_key(|&(index, _)| index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Common view over public trades and private fills
+// so the same analytics can consume either
+pub trait Execution {
+    fn price(&self) -> f64;
+    fn size(&self) -> f64;
+    fn side(&self) -> Side;
+    fn time(&self) -> DateTime<UTC>;
+}
+
+// A product identifier of the form `BASE-QUOTE`, e.g. `BTC-USD`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ProductId(String);
+
+impl ProductId {
+    pub fn new(id: &str) -> Result<ProductId, Error> {
+        fn is_currency(s: &str) -> bool {
+            s.len() >= 2 && s.chars().all(|c| match c {
+                'A'...'Z' | '0'...'9' => true,
+                _ => false
+            })
+        }
+
+        let valid = {
+            let mut parts = id.split('-');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(base), Some(quote), None) => is_currency(base) && is_currency(quote),
+                _ => false
+            }
+        };
+
+        if valid {
+            Ok(ProductId(id.to_owned()))
+        } else {
+            Err(Error::InvalidProductId(id.to_owned()))
+        }
+    }
+
+    pub fn base(&self) -> &str {
+        self.0.split('-').next().unwrap()
+    }
+
+    pub fn quote(&self) -> &str {
+        self.0.split('-').nth(1).unwrap()
+    }
+}
+
+impl AsRef<str> for ProductId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ProductId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ProductId, Error> {
+        ProductId::new(s)
+    }
+}
+
+impl fmt::Display for ProductId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Anything that can be turned into a `ProductId`. Strings are checked
+// on the way in, a `ProductId` has already been and passes straight
+// through, so a malformed id never makes it into a constructor.
+pub trait IntoProductId {
+    fn into_product_id(self) -> Result<ProductId, Error>;
+}
+
+impl IntoProductId for ProductId {
+    fn into_product_id(self) -> Result<ProductId, Error> {
+        Ok(self)
+    }
+}
+
+impl<'a> IntoProductId for &'a ProductId {
+    fn into_product_id(self) -> Result<ProductId, Error> {
+        Ok(self.clone())
+    }
+}
+
+impl<'a> IntoProductId for &'a str {
+    fn into_product_id(self) -> Result<ProductId, Error> {
+        ProductId::new(self)
+    }
+}
+
+impl<'a> IntoProductId for &'a String {
+    fn into_product_id(self) -> Result<ProductId, Error> {
+        ProductId::new(self)
+    }
+}
+
+impl IntoProductId for String {
+    fn into_product_id(self) -> Result<ProductId, Error> {
+        ProductId::new(&self)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Side {
     Buy,
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{Error, Side, SizeOrFunds};
+use super::private::{NewOrder, OpenOrder, OrderId};
+use super::public::{BookEntry, OrderBook, Product, Tick};
+use super::{PrivateClient, PublicClient};
+
+// A single entry point for an application trading several products.
+// Product metadata is fetched once and cached, as is the most recent
+// book seen for each product, and `product` hands out a handle scoped
+// to one product. Without a private client only market data works.
+pub struct Market {
+    public_client: PublicClient,
+    private_client: Option<PrivateClient>,
+    products: Mutex<HashMap<String, Product>>,
+    books: Mutex<HashMap<String, OrderBook<BookEntry>>>
+}
+
+impl Market {
+    pub fn new(public_client: PublicClient) -> Market {
+        Market {
+            public_client: public_client,
+            private_client: None,
+            products: Mutex::new(HashMap::new()),
+            books: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn with_private(private_client: PrivateClient) -> Market {
+        Market {
+            public_client: (*private_client).clone(),
+            private_client: Some(private_client),
+            products: Mutex::new(HashMap::new()),
+            books: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn public_client(&self) -> &PublicClient {
+        &self.public_client
+    }
+
+    pub fn private_client(&self) -> Option<&PrivateClient> {
+        self.private_client.as_ref()
+    }
+
+    // Replaces the cached product list, e.g. to pick up newly
+    // listed products or a change in a product's trading status
+    pub fn refresh_products(&self) -> Result<(), Error> {
+        let products = self.public_client.get_products()?;
+        *self.products.lock().unwrap() = products.into_iter()
+                                                 .map(|product| (product.id.clone(), product))
+                                                 .collect();
+        Ok(())
+    }
+
+    pub fn products(&self) -> Result<Vec<Product>, Error> {
+        self.load_products()?;
+        Ok(self.products.lock().unwrap().values().cloned().collect())
+    }
+
+    pub fn product(&self, id: &str) -> Result<ProductHandle, Error> {
+        self.load_products()?;
+        match self.products.lock().unwrap().get(id) {
+            Some(product) => Ok(ProductHandle { market: self, product: product.clone() }),
+            None => Err(Error::InvalidProductId(id.to_owned()))
+        }
+    }
+
+    fn load_products(&self) -> Result<(), Error> {
+        if self.products.lock().unwrap().is_empty() {
+            self.refresh_products()?;
+        }
+        Ok(())
+    }
+
+    fn private(&self) -> Result<&PrivateClient, Error> {
+        self.private_client.as_ref().ok_or_else(|| {
+            Error::InvalidArgument("market has no private client, see `Market::with_private`".to_owned())
+        })
+    }
+}
+
+pub struct ProductHandle<'a> {
+    market: &'a Market,
+    product: Product
+}
+
+impl<'a> ProductHandle<'a> {
+    pub fn product(&self) -> &Product {
+        &self.product
+    }
+
+    pub fn ticker(&self) -> Result<Tick, Error> {
+        self.market.public_client.get_product_ticker(&self.product.id)
+    }
+
+    // Fetches the top 50 levels and remembers them as the latest book
+    pub fn book(&self) -> Result<OrderBook<BookEntry>, Error> {
+        let book = self.market.public_client.get_top50_orders(&self.product.id)?;
+        self.market.books.lock().unwrap().insert(self.product.id.clone(), book.clone());
+        Ok(book)
+    }
+
+    // The book from the last call to `book`, without a request
+    pub fn last_book(&self) -> Option<OrderBook<BookEntry>> {
+        self.market.books.lock().unwrap().get(&self.product.id).cloned()
+    }
+
+    // Rounds price towards the passive side and size down to the
+    // product's increments, then validates before posting
+    pub fn place_limit(&self, side: Side, size: f64, price: f64) -> Result<OrderId, Error> {
+        let order = NewOrder::limit_unchecked(side,
+                                              &self.product.id,
+                                              self.product.round_size(size),
+                                              self.product.round_price_for_side(price, side));
+        self.place(&order)
+    }
+
+    pub fn place_market(&self, side: Side, size_or_funds: SizeOrFunds) -> Result<OrderId, Error> {
+        let size_or_funds = match size_or_funds {
+            SizeOrFunds::Size(size) => SizeOrFunds::Size(self.product.round_size(size)),
+            funds => funds
+        };
+        self.place(&NewOrder::market_unchecked(side, &self.product.id, size_or_funds))
+    }
+
+    pub fn place(&self, order: &NewOrder) -> Result<OrderId, Error> {
+        order.validate(&self.product)?;
+        self.market.private()?.post_order(order)
+    }
+
+    pub fn open_orders(&self) -> Result<Vec<OpenOrder>, Error> {
+        let orders = self.market.private()?.all_open_orders()?;
+        Ok(orders.into_iter().filter(|order| order.product_id == self.product.id).collect())
+    }
+
+    pub fn cancel_all(&self) -> Result<Vec<OrderId>, Error> {
+        self.market.private()?.cancel_all_orders(Some(&self.product.id))
+    }
+}
@@ -0,0 +1,65 @@
+use serde;
+
+// GDAX occasionally sends `""`, `"NaN"` or `null` where a number belongs,
+// usually for the volume of a product that only just listed. These are
+// used via `deserialize_with` so that one bad field neither fails the
+// whole response nor leaks a NaN into downstream arithmetic.
+
+// Anything that isn't a finite number becomes 0
+pub fn f64_or_zero<D>(deserializer: &mut D) -> Result<f64, D::Error>
+    where D: serde::Deserializer
+{
+    Ok(opt_f64(deserializer)?.unwrap_or(0.))
+}
+
+// Anything that isn't a finite number becomes `None`
+pub fn opt_f64<D>(deserializer: &mut D) -> Result<Option<f64>, D::Error>
+    where D: serde::Deserializer
+{
+    deserializer.deserialize(LenientF64Visitor)
+}
+
+fn finite(v: f64) -> Option<f64> {
+    if v.is_finite() { Some(v) } else { None }
+}
+
+struct LenientF64Visitor;
+
+impl serde::de::Visitor for LenientF64Visitor {
+    type Value = Option<f64>;
+
+    fn visit_f64<E>(&mut self, v: f64) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(finite(v))
+    }
+
+    fn visit_i64<E>(&mut self, v: i64) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(Some(v as f64))
+    }
+
+    fn visit_u64<E>(&mut self, v: u64) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(Some(v as f64))
+    }
+
+    fn visit_str<E>(&mut self, v: &str) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(v.trim().parse().ok().and_then(finite))
+    }
+
+    fn visit_unit<E>(&mut self) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(None)
+    }
+
+    fn visit_none<E>(&mut self) -> Result<Self::Value, E>
+        where E: serde::Error {
+        Ok(None)
+    }
+
+    fn visit_some<D>(&mut self, deserializer: &mut D) -> Result<Self::Value, D::Error>
+        where D: serde::Deserializer {
+        opt_f64(deserializer)
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use super::Side;
+use super::private::Fill;
+
+// Below this a position is considered flat, so float
+// error doesn't leave a dust-sized lot open forever
+const SIZE_EPSILON: f64 = 1e-12;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccountingMethod {
+    // Each close is matched against the oldest open lot first
+    Fifo,
+    // Open lots are pooled at their size-weighted average price
+    AverageCost
+}
+
+// Size is signed, positive for a long lot and negative for a short one
+#[derive(Clone, Copy, Debug)]
+struct Lot {
+    price: f64,
+    size: f64
+}
+
+// Realized PnL of a single product's fills, in the quote currency and
+// net of fees. Fills are expected in the order they happened; a fill
+// that flips the position closes it fully before opening the other way.
+pub struct PnlCalculator {
+    method: AccountingMethod,
+    lots: VecDeque<Lot>,
+    realized: f64,
+    fees: f64,
+    closed_size: f64,
+    closed_notional: f64
+}
+
+impl PnlCalculator {
+    pub fn new(method: AccountingMethod) -> PnlCalculator {
+        PnlCalculator {
+            method: method,
+            lots: VecDeque::new(),
+            realized: 0.,
+            fees: 0.,
+            closed_size: 0.,
+            closed_notional: 0.
+        }
+    }
+
+    pub fn add(&mut self, fill: &Fill) {
+        let mut remaining = match fill.side {
+            Side::Buy => fill.size,
+            Side::Sell => -fill.size
+        };
+        self.fees += fill.fee;
+
+        while remaining.abs() > SIZE_EPSILON {
+            let closed = {
+                let lot = match self.lots.front_mut() {
+                    Some(lot) if lot.size.signum() != remaining.signum() => lot,
+                    _ => break
+                };
+
+                let size = remaining.abs().min(lot.size.abs());
+                self.realized += size * (fill.price - lot.price) * lot.size.signum();
+                self.closed_size += size;
+                self.closed_notional += size * fill.price;
+
+                lot.size -= size * lot.size.signum();
+                remaining -= size * remaining.signum();
+
+                lot.size.abs() <= SIZE_EPSILON
+            };
+
+            if closed {
+                self.lots.pop_front();
+            }
+        }
+
+        if remaining.abs() > SIZE_EPSILON {
+            self.open(Lot { price: fill.price, size: remaining });
+        }
+    }
+
+    pub fn extend<'a, I>(&mut self, fills: I)
+        where I: IntoIterator<Item = &'a Fill>
+    {
+        for fill in fills {
+            self.add(fill);
+        }
+    }
+
+    fn open(&mut self, lot: Lot) {
+        if self.method == AccountingMethod::AverageCost {
+            if let Some(pooled) = self.lots.back_mut() {
+                let size = pooled.size + lot.size;
+                pooled.price = (pooled.price * pooled.size + lot.price * lot.size) / size;
+                pooled.size = size;
+                return;
+            }
+        }
+        self.lots.push_back(lot);
+    }
+
+    // Profit on the closed part of the position less every fee paid
+    pub fn realized(&self) -> f64 {
+        self.realized - self.fees
+    }
+
+    pub fn fees(&self) -> f64 {
+        self.fees
+    }
+
+    // Net open size, negative when short
+    pub fn position(&self) -> f64 {
+        self.lots.iter().fold(0., |size, lot| size + lot.size)
+    }
+
+    // Average price of what's still open, `None` when flat
+    pub fn average_entry(&self) -> Option<f64> {
+        let (notional, size) = self.lots.iter().fold((0., 0.), |(notional, size), lot| {
+            (notional + lot.price * lot.size.abs(), size + lot.size.abs())
+        });
+        if size > SIZE_EPSILON {
+            Some(notional / size)
+        } else {
+            None
+        }
+    }
+
+    // Average price at which size has been closed so far
+    pub fn average_exit(&self) -> Option<f64> {
+        if self.closed_size > SIZE_EPSILON {
+            Some(self.closed_notional / self.closed_size)
+        } else {
+            None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = PnlCalculator::new(self.method);
+    }
+}
@@ -0,0 +1,5 @@
+// Commonly used types and traits, for `use gdax_client::prelude::*`
+
+pub use super::{Cursor, Error, Execution, FailureMode, IntoProductId, Page, PaginatedStream, ProductId, Side};
+pub use super::{ClientBuilder, PrivateClient, PublicClient};
+pub use super::{AccountsExt, LedgerExt, NewOrder, OrderError, OrderLike, OrderStatus, SizeOrFunds};
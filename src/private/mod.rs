@@ -1,31 +1,129 @@
-use base64;
 use chrono::{DateTime, UTC};
-use crypto::hmac::Hmac;
-use crypto::mac::Mac;
-use crypto::sha2::Sha256;
 use hyper::client::Client as HttpClient;
-use hyper::header::{Accept, ContentType, Headers, qitem, UserAgent};
+use hyper::header::{Accept, Connection, ContentType, Headers, qitem, UserAgent};
+use hyper::method::Method;
 use hyper::mime::{Mime, TopLevel, SubLevel};
 use serde::{self, Deserialize, Serialize};
 use serde_json::{de, ser};
+use serde_json::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
 use std::ops::Deref;
-use time::get_time;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use super::{Cursor, Page, PaginatedStream};
 use super::Error;
+use super::Execution;
+use super::FailureMode;
+use super::builder::Config;
+use super::journal::OrderJournal;
+use super::{IntoProductId, ProductId};
 use super::Side;
+use super::rate_limit::{RateLimiter, RateLimitStatus};
+use super::signing::{check_secret, sign, Timestamper};
+use super::public::Product;
 
-const PRIVATE_API_URL: &'static str = "https://api.gdax.com";
+const KEY_VAR: &'static str = "CB_KEY";
+const SECRET_VAR: &'static str = "CB_SECRET";
+const PASSPHRASE_VAR: &'static str = "CB_PASSPHRASE";
 
+// Clones share the same connection pool, rate limiter and timestamper,
+// the last so that requests signed from any clone never reuse a timestamp
+#[derive(Clone)]
 pub struct Client {
     public_client: super::public::Client,
-    http_client: HttpClient,
+    http_client: Arc<HttpClient>,
     key: String,
     secret: String,
-    passphrase: String
+    passphrase: String,
+    timestamper: Arc<Timestamper>,
+    rate_limiter: Arc<RateLimiter>,
+    cancel_dedup: Option<Arc<CancelDedup>>,
+    order_retries: Option<u32>,
+    default_headers: Headers,
+    config: Arc<Config>,
+    journal: Option<Arc<OrderJournal>>,
+    // Highest trade id per product already passed to the journal
+    journaled_fills: Arc<Mutex<HashMap<String, u64>>>,
+    balance_check: bool
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Copy, Debug)]
+enum CancelState {
+    // A DELETE for the order is on its way
+    InFlight,
+    // The exchange confirmed the cancel
+    Cancelled(Instant),
+    // The DELETE failed in transit, so it may or may not have landed
+    Unconfirmed(Instant)
+}
+
+// Keeps `cancel_order` from sending more than one DELETE for the same
+// order within `window`. A call made while another is in flight waits
+// for it and reuses its outcome if it succeeded. A cancel that failed in
+// transit is sent again, but an "order not found" or "already done"
+// answer then means the first DELETE went through and counts as success.
+struct CancelDedup {
+    window: Duration,
+    states: Mutex<HashMap<OrderId, CancelState>>,
+    finished: Condvar
+}
+
+impl CancelDedup {
+    fn new(window: Duration) -> CancelDedup {
+        CancelDedup {
+            window: window,
+            states: Mutex::new(HashMap::new()),
+            finished: Condvar::new()
+        }
+    }
+
+    // Waits for an in-flight cancel of the order to finish, then returns
+    // what's known about earlier cancels. Unless the order is already
+    // cancelled it's marked in flight, and `finish` has to follow.
+    fn begin(&self, order_id: OrderId) -> Option<CancelState> {
+        let mut states = self.states.lock().unwrap();
+        loop {
+            let window = self.window;
+            states.retain(|_, state| match *state {
+                CancelState::InFlight => true,
+                CancelState::Cancelled(at) | CancelState::Unconfirmed(at) => at.elapsed() < window
+            });
+
+            let previous = states.get(&order_id).cloned();
+            match previous {
+                Some(CancelState::InFlight) => states = self.finished.wait(states).unwrap(),
+                Some(CancelState::Cancelled(_)) => return previous,
+                _ => {
+                    states.insert(order_id, CancelState::InFlight);
+                    return previous;
+                }
+            }
+        }
+    }
+
+    fn finish(&self, order_id: OrderId, result: &Result<OrderId, Error>) {
+        let mut states = self.states.lock().unwrap();
+        match *result {
+            Ok(_) => {
+                states.insert(order_id, CancelState::Cancelled(Instant::now()));
+            }
+            Err(Error::Connection(_)) => {
+                states.insert(order_id, CancelState::Unconfirmed(Instant::now()));
+            }
+            Err(_) => {
+                states.remove(&order_id);
+            }
+        }
+        self.finished.notify_all();
+    }
+}
+
+#[derive(Clone, Deserialize, Debug)]
 pub struct Account {
     pub id: Uuid,
     pub balance: f64,
@@ -34,29 +132,255 @@ pub struct Account {
     pub currency: String
 }
 
+pub trait AccountsExt {
+    fn by_currency(&self, currency: &str) -> Option<&Account>;
+    fn total_balance(&self) -> HashMap<String, f64>;
+    fn nonzero(&self) -> Vec<&Account>;
+}
+
+impl AccountsExt for [Account] {
+    fn by_currency(&self, currency: &str) -> Option<&Account> {
+        self.iter().find(|account| account.currency == currency)
+    }
+
+    fn total_balance(&self) -> HashMap<String, f64> {
+        let mut totals = HashMap::new();
+        for account in self {
+            *totals.entry(account.currency.clone()).or_insert(0.) += account.balance;
+        }
+        totals
+    }
+
+    fn nonzero(&self) -> Vec<&Account> {
+        self.iter().filter(|account| account.balance != 0.).collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct AccountDelta {
+    pub currency: String,
+    pub old: Option<Account>,
+    pub new: Account
+}
+
+// Iterator returned by `Client::account_changes`. The first poll only
+// records a baseline, after that each item is the set of accounts whose
+// balance, availability or hold changed since the previous poll.
+pub struct AccountChanges<'a> {
+    client: &'a Client,
+    poll_interval: Duration,
+    last: Option<HashMap<Uuid, Account>>
+}
+
+impl<'a> Iterator for AccountChanges<'a> {
+    type Item = Result<Vec<AccountDelta>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.last.is_some() {
+                thread::sleep(self.poll_interval);
+            }
+
+            let accounts = match self.client.get_accounts() {
+                Ok(accounts) => accounts,
+                Err(err) => return Some(Err(err))
+            };
+
+            let mut deltas = Vec::new();
+            if let Some(ref last) = self.last {
+                for account in &accounts {
+                    let old = last.get(&account.id);
+                    let changed = match old {
+                        Some(old) => old.balance != account.balance ||
+                                     old.available != account.available ||
+                                     old.hold != account.hold,
+                        None => true
+                    };
+
+                    if changed {
+                        deltas.push(AccountDelta {
+                            currency: account.currency.clone(),
+                            old: old.cloned(),
+                            new: account.clone()
+                        });
+                    }
+                }
+            }
+
+            self.last = Some(accounts.into_iter().map(|account| (account.id, account)).collect());
+
+            if !deltas.is_empty() {
+                return Some(Ok(deltas));
+            }
+        }
+    }
+}
+
 pub type Ledger = Vec<LedgerEntry>;
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug)]
 pub struct LedgerEntry {
     pub id: u64,
     pub created_at: DateTime<UTC>,
     pub amount: f64,
     pub balance: f64,
-    #[serde(rename = "type")]
     pub entry_type: EntryType,
     pub details: Option<EntryDetails>
 }
 
-#[derive(Deserialize, Debug)]
-pub struct EntryDetails {
-    pub order_id: Option<Uuid>,
-    pub trade_id: Option<u64>,
-    pub product_id: Option<String>,
-    pub transfer_id: Option<Uuid>,
-    pub transfer_type: Option<String>
+// The ledger entry as it comes over the wire, `details`
+// can only be typed once we know the kind of entry
+#[derive(Deserialize)]
+struct RawLedgerEntry {
+    id: u64,
+    created_at: DateTime<UTC>,
+    amount: f64,
+    balance: f64,
+    #[serde(rename = "type")]
+    entry_type: EntryType,
+    details: Option<RawEntryDetails>
+}
+
+#[derive(Deserialize)]
+struct RawEntryDetails {
+    order_id: Option<Uuid>,
+    trade_id: Option<u64>,
+    product_id: Option<String>,
+    transfer_id: Option<Uuid>,
+    transfer_type: Option<TransferType>
+}
+
+// We manually implement Deserialize for LedgerEntry here
+// because the shape of `details` depends on the entry type
+impl serde::Deserialize for LedgerEntry {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LedgerEntry, D::Error>
+        where D: serde::Deserializer {
+
+        let raw = RawLedgerEntry::deserialize(deserializer)?;
+        let details = match raw.details {
+            Some(details) => Some(EntryDetails::from_raw(raw.entry_type, details)?),
+            None => None
+        };
+
+        Ok(LedgerEntry {
+            id: raw.id,
+            created_at: raw.created_at,
+            amount: raw.amount,
+            balance: raw.balance,
+            entry_type: raw.entry_type,
+            details: details
+        })
+    }
 }
 
 #[derive(Debug)]
+pub struct LedgerInconsistency {
+    pub entry_id: u64,
+    pub expected_balance: f64,
+    pub balance: f64
+}
+
+pub trait LedgerExt {
+    fn verify_running_balance(&self) -> Result<(), LedgerInconsistency>;
+}
+
+impl LedgerExt for [LedgerEntry] {
+    // Walks the entries oldest first and checks each balance is
+    // the previous balance plus the entry's amount. The first entry
+    // is taken as the starting point since nothing precedes it.
+    fn verify_running_balance(&self) -> Result<(), LedgerInconsistency> {
+        const EPSILON: f64 = 1e-8;
+
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| (entry.created_at, entry.id));
+
+        for pair in entries.windows(2) {
+            let expected_balance = pair[0].balance + pair[1].amount;
+            if (expected_balance - pair[1].balance).abs() > EPSILON {
+                return Err(LedgerInconsistency {
+                    entry_id: pair[1].id,
+                    expected_balance: expected_balance,
+                    balance: pair[1].balance
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum EntryDetails {
+    Fee {
+        order_id: Option<Uuid>,
+        trade_id: Option<u64>,
+        product_id: Option<String>
+    },
+    Match {
+        order_id: Uuid,
+        trade_id: u64,
+        product_id: String
+    },
+    Transfer {
+        transfer_id: Uuid,
+        transfer_type: TransferType
+    }
+}
+
+impl EntryDetails {
+    fn from_raw<E>(entry_type: EntryType, raw: RawEntryDetails) -> Result<EntryDetails, E>
+        where E: serde::Error {
+
+        match entry_type {
+            EntryType::Fee => Ok(EntryDetails::Fee {
+                order_id: raw.order_id,
+                trade_id: raw.trade_id,
+                product_id: raw.product_id
+            }),
+            EntryType::Match => Ok(EntryDetails::Match {
+                order_id: raw.order_id.ok_or_else(|| E::missing_field("order_id"))?,
+                trade_id: raw.trade_id.ok_or_else(|| E::missing_field("trade_id"))?,
+                product_id: raw.product_id.ok_or_else(|| E::missing_field("product_id"))?
+            }),
+            EntryType::Transfer => Ok(EntryDetails::Transfer {
+                transfer_id: raw.transfer_id.ok_or_else(|| E::missing_field("transfer_id"))?,
+                transfer_type: raw.transfer_type.ok_or_else(|| E::missing_field("transfer_type"))?
+            })
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransferType {
+    Deposit,
+    Withdraw
+}
+
+// We manually implement Deserialize for TransferType here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl serde::Deserialize for TransferType {
+    fn deserialize<D>(deserializer: &mut D) -> Result<TransferType, D::Error>
+        where D: serde::Deserializer {
+
+        struct TransferTypeVisitor;
+        impl serde::de::Visitor for TransferTypeVisitor {
+            type Value = TransferType;
+
+            fn visit_str<E>(&mut self, v: &str) -> Result<Self::Value, E>
+                where E: serde::Error {
+                match &*v.to_lowercase() {
+                    "deposit" => Ok(TransferType::Deposit),
+                    "withdraw" => Ok(TransferType::Withdraw),
+                    _ => Err(E::invalid_value("transfer type must be either `deposit` or `withdraw`"))
+                }
+            }
+        }
+        deserializer.deserialize(TransferTypeVisitor)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum EntryType {
     Fee,
     Match,
@@ -101,7 +425,24 @@ pub struct Hold {
     pub ref_id: Uuid
 }
 
-#[derive(Debug)]
+// A deposit into or withdrawal from an account. A transfer that is
+// still in flight has neither `processed_at` nor `canceled_at` set.
+#[derive(Deserialize, Debug)]
+pub struct Transfer {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub transfer_type: TransferType,
+    pub amount: f64,
+    pub created_at: DateTime<UTC>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<UTC>>,
+    #[serde(default)]
+    pub canceled_at: Option<DateTime<UTC>>,
+    #[serde(default)]
+    pub processed_at: Option<DateTime<UTC>>
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum HoldType {
     Order,
     Transfer
@@ -133,57 +474,506 @@ impl serde::Deserialize for HoldType {
 
 pub type OrderId = Uuid;
 
+// Which way the price has to move to trigger a stop: a loss stop
+// fires when the price falls to it, an entry stop when it rises
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StopDirection {
+    Loss,
+    Entry
+}
+
+impl StopDirection {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StopDirection::Loss => "loss",
+            StopDirection::Entry => "entry"
+        }
+    }
+
+    // A sell stop usually protects a long position and a buy stop
+    // usually enters on a breakout, which is what `stop` assumes
+    pub fn default_for(side: Side) -> StopDirection {
+        match side {
+            Side::Sell => StopDirection::Loss,
+            Side::Buy => StopDirection::Entry
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum SizeOrFunds {
     Size(f64),
     Funds(f64)
 }
 
-#[derive(Debug)]
+impl SizeOrFunds {
+    pub fn as_size(&self) -> Option<f64> {
+        match *self {
+            SizeOrFunds::Size(size) => Some(size),
+            SizeOrFunds::Funds(_) => None
+        }
+    }
+
+    pub fn as_funds(&self) -> Option<f64> {
+        match *self {
+            SizeOrFunds::Size(_) => None,
+            SizeOrFunds::Funds(funds) => Some(funds)
+        }
+    }
+}
+
+// Always labelled, e.g. "1.0 (size)", since a bare number
+// doesn't say whether it's in base or quote currency
+impl fmt::Display for SizeOrFunds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SizeOrFunds::Size(size) => write!(f, "{:?} (size)", size),
+            SizeOrFunds::Funds(funds) => write!(f, "{:?} (funds)", funds)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum NewOrder {
     Limit {
         side: Side,
         product_id: String,
         price: f64,
-        size: f64
+        size: f64,
+        post_only: bool,
+        client_oid: Option<Uuid>
     },
     Market {
         side: Side,
         product_id: String,
         size_or_funds: SizeOrFunds,
+        client_oid: Option<Uuid>
     },
     Stop {
         side: Side,
         product_id: String,
         price: f64,
-        size_or_funds: SizeOrFunds
+        size_or_funds: SizeOrFunds,
+        direction: StopDirection,
+        client_oid: Option<Uuid>
     }
 }
 
 impl NewOrder {
-    pub fn limit(side: Side, product_id: &str, size: f64, price: f64) -> NewOrder {
+    pub fn limit<P>(side: Side, product_id: P, size: f64, price: f64) -> Result<NewOrder, Error>
+        where P: IntoProductId
+    {
+        let product_id = product_id.into_product_id()?;
+        Ok(NewOrder::limit_unchecked(side, product_id.as_ref(), size, price))
+    }
+
+    pub fn market<P>(side: Side, product_id: P, size_or_funds: SizeOrFunds) -> Result<NewOrder, Error>
+        where P: IntoProductId
+    {
+        let product_id = product_id.into_product_id()?;
+        Ok(NewOrder::market_unchecked(side, product_id.as_ref(), size_or_funds))
+    }
+
+    // Like `market`, but also rejects the unusual denominations:
+    // a buy is normally given in funds and a sell in size, so
+    // the other way around is more often a mistake than not
+    pub fn market_strict<P>(side: Side, product_id: P, size_or_funds: SizeOrFunds) -> Result<NewOrder, Error>
+        where P: IntoProductId
+    {
+        match (side, size_or_funds) {
+            (Side::Buy, SizeOrFunds::Size(_)) => {
+                Err(Error::InvalidOrder(OrderError::MarketBuyInSize))
+            }
+            (Side::Sell, SizeOrFunds::Funds(_)) => {
+                Err(Error::InvalidOrder(OrderError::MarketSellInFunds))
+            }
+            _ => NewOrder::market(side, product_id, size_or_funds)
+        }
+    }
+
+    pub fn stop<P>(side: Side, product_id: P, size_or_funds: SizeOrFunds, price: f64) -> Result<NewOrder, Error>
+        where P: IntoProductId
+    {
+        let product_id = product_id.into_product_id()?;
+        Ok(NewOrder::stop_unchecked(side, product_id.as_ref(), size_or_funds, price))
+    }
+
+    pub fn limit_unchecked(side: Side, product_id: &str, size: f64, price: f64) -> NewOrder {
         NewOrder::Limit {
             side: side,
             product_id: product_id.to_owned(),
             price: price,
-            size: size
+            size: size,
+            post_only: false,
+            client_oid: None
         }
     }
 
-    pub fn market(side: Side, product_id: &str, size_or_funds: SizeOrFunds) -> NewOrder {
+    pub fn market_unchecked(side: Side, product_id: &str, size_or_funds: SizeOrFunds) -> NewOrder {
         NewOrder::Market {
             side: side,
             product_id: product_id.to_owned(),
-            size_or_funds: size_or_funds
+            size_or_funds: size_or_funds,
+            client_oid: None
         }
     }
 
-    pub fn stop(side: Side, product_id: &str, size_or_funds: SizeOrFunds, price: f64) -> NewOrder {
+    pub fn stop_unchecked(side: Side, product_id: &str, size_or_funds: SizeOrFunds, price: f64) -> NewOrder {
         NewOrder::Stop {
             side: side,
             product_id: product_id.to_owned(),
             size_or_funds: size_or_funds,
-            price: price
+            price: price,
+            direction: StopDirection::default_for(side),
+            client_oid: None
+        }
+    }
+
+    // Overrides the direction a stop order picked by default
+    // from its side. Has no effect on other kinds of orders.
+    pub fn with_stop_direction(mut self, new_direction: StopDirection) -> NewOrder {
+        if let NewOrder::Stop { ref mut direction, .. } = self {
+            *direction = new_direction;
+        }
+        self
+    }
+
+    // Has a limit order rejected rather than filled, even partly, if it
+    // would cross the spread, so it only ever adds liquidity. Has no
+    // effect on other kinds of orders.
+    pub fn with_post_only(mut self) -> NewOrder {
+        if let NewOrder::Limit { ref mut post_only, .. } = self {
+            *post_only = true;
+        }
+        self
+    }
+
+    // Tags the order with an id of our choosing, which GDAX echoes
+    // back and lets us look the order up by before we know its id
+    pub fn with_client_oid(mut self, oid: Uuid) -> NewOrder {
+        match self {
+            NewOrder::Limit { ref mut client_oid, .. } |
+            NewOrder::Market { ref mut client_oid, .. } |
+            NewOrder::Stop { ref mut client_oid, .. } => *client_oid = Some(oid)
+        }
+        self
+    }
+
+    pub fn client_oid(&self) -> Option<Uuid> {
+        match *self {
+            NewOrder::Limit { client_oid, .. } |
+            NewOrder::Market { client_oid, .. } |
+            NewOrder::Stop { client_oid, .. } => client_oid
+        }
+    }
+
+    // Value of the order in the quote currency. A market order given in
+    // size has no price of its own so it needs `reference_price`, e.g.
+    // the current ticker price, and is `None` without one.
+    pub fn notional(&self, reference_price: Option<f64>) -> Option<f64> {
+        match *self {
+            NewOrder::Limit { price, size, .. } |
+            NewOrder::Stop { price, size_or_funds: SizeOrFunds::Size(size), .. } => Some(size * price),
+            NewOrder::Market { size_or_funds: SizeOrFunds::Funds(funds), .. } |
+            NewOrder::Stop { size_or_funds: SizeOrFunds::Funds(funds), .. } => Some(funds),
+            NewOrder::Market { size_or_funds: SizeOrFunds::Size(size), .. } => {
+                reference_price.map(|price| size * price)
+            }
+        }
+    }
+
+    // Exchange rules that hold for every product, checked
+    // before any order is posted
+    fn check(&self) -> Result<(), Error> {
+        match *self {
+            NewOrder::Market { side: Side::Sell, size_or_funds: SizeOrFunds::Funds(_), .. } => {
+                Err(Error::InvalidOrder(OrderError::MarketSellInFunds))
+            }
+            _ => Ok(())
+        }
+    }
+
+    // Checks the order against the product's trading rules
+    // so a bad order is caught before making a round trip
+    pub fn validate(&self, product: &Product) -> Result<(), Error> {
+        self.check()?;
+
+        let (product_id, price, size, funds) = match *self {
+            NewOrder::Limit { ref product_id, price, size, .. } => (product_id, Some(price), Some(size), None),
+            NewOrder::Market { ref product_id, size_or_funds, .. } => {
+                (product_id, None, size_or_funds.as_size(), size_or_funds.as_funds())
+            }
+            NewOrder::Stop { ref product_id, price, size_or_funds, .. } => {
+                (product_id, Some(price), size_or_funds.as_size(), None)
+            }
+        };
+
+        if *product_id != product.id {
+            return Err(Error::InvalidOrder(OrderError::ProductMismatch {
+                expected: product.id.clone(),
+                got: product_id.clone()
+            }));
+        }
+
+        // NaN compares false against every limit below, so
+        // it has to be ruled out before any of them
+        let amount = |size_or_funds: SizeOrFunds| match size_or_funds {
+            SizeOrFunds::Size(size) => ("size", size),
+            SizeOrFunds::Funds(funds) => ("funds", funds)
+        };
+        let amounts = match *self {
+            NewOrder::Limit { price, size, .. } => vec![("price", price), ("size", size)],
+            NewOrder::Market { size_or_funds, .. } => vec![amount(size_or_funds)],
+            NewOrder::Stop { price, size_or_funds, .. } => vec![("price", price), amount(size_or_funds)]
+        };
+        for (field, value) in amounts {
+            if !value.is_finite() {
+                return Err(Error::InvalidOrder(OrderError::NotFinite { field: field, got: value }));
+            }
+            if value <= 0. {
+                return Err(Error::InvalidOrder(OrderError::NotPositive { field: field, got: value }));
+            }
+        }
+
+        if let Some(size) = size {
+            if size < product.base_min_size {
+                return Err(Error::InvalidOrder(OrderError::SizeBelowMinimum {
+                    min: product.base_min_size,
+                    got: size
+                }));
+            }
+            if size > product.base_max_size {
+                return Err(Error::InvalidOrder(OrderError::SizeAboveMaximum {
+                    max: product.base_max_size,
+                    got: size
+                }));
+            }
+            if (product.round_size(size) - size).abs() > 1e-9 {
+                return Err(Error::InvalidOrder(OrderError::SizeNotOnIncrement {
+                    increment: product.base_increment,
+                    got: size
+                }));
+            }
+        }
+
+        if let Some(funds) = funds {
+            if let Some(min) = product.min_market_funds {
+                if funds < min {
+                    return Err(Error::InvalidOrder(OrderError::FundsBelowMinimum { min: min, got: funds }));
+                }
+            }
+            if let Some(max) = product.max_market_funds {
+                if funds > max {
+                    return Err(Error::InvalidOrder(OrderError::FundsAboveMaximum { max: max, got: funds }));
+                }
+            }
+        }
+
+        if let Some(price) = price {
+            if (product.round_price(price) - price).abs() > 1e-9 {
+                return Err(Error::InvalidOrder(OrderError::PriceNotOnIncrement {
+                    increment: product.quote_increment,
+                    got: price
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    // The order divided into `parts` child orders, each rounded down to
+    // the product's increment with whatever that leaves over added to the
+    // last one. Fails if any child breaks the product's trading rules,
+    // e.g. by ending up below the minimum size.
+    fn split(&self, product: &Product, parts: u32) -> Result<Vec<NewOrder>, Error> {
+        let (amount, increment) = match *self {
+            NewOrder::Limit { size, .. } |
+            NewOrder::Market { size_or_funds: SizeOrFunds::Size(size), .. } |
+            NewOrder::Stop { size_or_funds: SizeOrFunds::Size(size), .. } => (size, product.base_increment),
+            NewOrder::Market { size_or_funds: SizeOrFunds::Funds(funds), .. } |
+            NewOrder::Stop { size_or_funds: SizeOrFunds::Funds(funds), .. } => (funds, product.quote_increment)
+        };
+
+        let slice = amount / parts as f64;
+        let slice = if increment > 0. { (slice / increment + 1e-9).floor() * increment } else { slice };
+        let last = amount - slice * (parts - 1) as f64;
+
+        (0..parts).map(|part| {
+            let child = self.with_amount(if part + 1 == parts { last } else { slice });
+            child.validate(product)?;
+            Ok(child)
+        }).collect()
+    }
+
+    // A copy of the order for a different size or amount of funds.
+    // The copy is a separate order so it doesn't keep the `client_oid`.
+    fn with_amount(&self, amount: f64) -> NewOrder {
+        let mut order = self.clone();
+        match order {
+            NewOrder::Limit { ref mut size, ref mut client_oid, .. } => {
+                *size = amount;
+                *client_oid = None;
+            }
+            NewOrder::Market { ref mut size_or_funds, ref mut client_oid, .. } |
+            NewOrder::Stop { ref mut size_or_funds, ref mut client_oid, .. } => {
+                *size_or_funds = match *size_or_funds {
+                    SizeOrFunds::Size(_) => SizeOrFunds::Size(amount),
+                    SizeOrFunds::Funds(_) => SizeOrFunds::Funds(amount)
+                };
+                *client_oid = None;
+            }
+        }
+        order
+    }
+}
+
+// What's wrong with an order that was rejected before being posted.
+// Carries the offending value along with the limit it broke so a
+// caller can point at the field and suggest a fix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderError {
+    ProductMismatch { expected: String, got: String },
+    // `field` is one of "price", "size" or "funds"
+    NotFinite { field: &'static str, got: f64 },
+    NotPositive { field: &'static str, got: f64 },
+    SizeBelowMinimum { min: f64, got: f64 },
+    SizeAboveMaximum { max: f64, got: f64 },
+    SizeNotOnIncrement { increment: f64, got: f64 },
+    FundsBelowMinimum { min: f64, got: f64 },
+    FundsAboveMaximum { max: f64, got: f64 },
+    PriceNotOnIncrement { increment: f64, got: f64 },
+    // Only rejected by `market_strict`
+    MarketBuyInSize,
+    MarketSellInFunds
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OrderError::ProductMismatch { ref expected, ref got } => {
+                write!(f, "order is for {} but product is {}", got, expected)
+            }
+            OrderError::NotFinite { field, got } => write!(f, "{} {} is not a finite number", field, got),
+            OrderError::NotPositive { field, got } => write!(f, "{} {} must be positive", field, got),
+            OrderError::SizeBelowMinimum { min, got } => {
+                write!(f, "size {} is below the minimum of {}", got, min)
+            }
+            OrderError::SizeAboveMaximum { max, got } => {
+                write!(f, "size {} is above the maximum of {}", got, max)
+            }
+            OrderError::SizeNotOnIncrement { increment, got } => {
+                write!(f, "size {} is not a multiple of {}", got, increment)
+            }
+            OrderError::FundsBelowMinimum { min, got } => {
+                write!(f, "funds {} are below the minimum of {}", got, min)
+            }
+            OrderError::FundsAboveMaximum { max, got } => {
+                write!(f, "funds {} are above the maximum of {}", got, max)
+            }
+            OrderError::PriceNotOnIncrement { increment, got } => {
+                write!(f, "price {} is not a multiple of {}", got, increment)
+            }
+            OrderError::MarketBuyInSize => write!(f, "market buy should be specified in funds"),
+            OrderError::MarketSellInFunds => write!(f, "market sell must be specified in size, not funds")
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Spacing {
+    // The same price step between every level
+    Linear,
+    // The same ratio between every level, so steps widen with the price
+    Geometric
+}
+
+// Splits `total_size` evenly over `levels` limit orders spread from
+// `price_low` to `price_high`, nearest the market first: buys from the
+// top of the range down, sells from the bottom up. Prices are snapped
+// to the product's increment towards the passive side and sizes rounded
+// down, so the orders can add up to slightly less than `total_size`.
+// Levels that snap to the same price are combined into one order.
+// Fails if an order would break the product's trading rules, e.g. when
+// `total_size` spread over `levels` comes out below the minimum size.
+// The range has to be finite with `price_low` no higher than
+// `price_high`, and geometric spacing needs a positive `price_low`.
+pub fn ladder(side: Side,
+              product: &Product,
+              total_size: f64,
+              price_low: f64,
+              price_high: f64,
+              levels: u32,
+              spacing: Spacing)
+    -> Result<Vec<NewOrder>, Error> {
+
+    if !price_low.is_finite() || !price_high.is_finite() {
+        return Err(Error::InvalidArgument(format!("price range {} to {} is not finite", price_low, price_high)));
+    }
+    if price_low > price_high {
+        return Err(Error::InvalidArgument(format!("price_low {} is above price_high {}", price_low, price_high)));
+    }
+    if spacing == Spacing::Geometric && price_low <= 0. {
+        return Err(Error::InvalidArgument(format!("geometric spacing needs a positive price_low, got {}",
+                                                  price_low)));
+    }
+
+    if levels == 0 {
+        return Ok(Vec::new());
+    }
+
+    let size = product.round_size(total_size / levels as f64);
+    let mut rungs: Vec<(f64, f64)> = Vec::new();
+
+    for level in 0..levels {
+        let fraction = if levels == 1 { 0. } else { level as f64 / (levels - 1) as f64 };
+        let fraction = match side {
+            Side::Buy => 1. - fraction,
+            Side::Sell => fraction
+        };
+        let price = match spacing {
+            Spacing::Linear => price_low + (price_high - price_low) * fraction,
+            Spacing::Geometric => price_low * (price_high / price_low).powf(fraction)
+        };
+        let price = product.round_price_for_side(price, side);
+
+        if let Some(last) = rungs.last_mut() {
+            if last.0 == price {
+                last.1 += size;
+                continue;
+            }
+        }
+        rungs.push((price, size));
+    }
+
+    rungs.into_iter().map(|(price, size)| {
+        let order = NewOrder::limit_unchecked(side, &product.id, product.round_size(size), price);
+        order.validate(product)?;
+        Ok(order)
+    }).collect()
+}
+
+impl fmt::Display for NewOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NewOrder::Limit { side, ref product_id, price, size, post_only, .. } => {
+                write!(f, "{} {} {} @ limit {}", side.to_string().to_uppercase(), size, product_id, price)?;
+                if post_only {
+                    write!(f, " (post_only)")?;
+                }
+                Ok(())
+            }
+            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Size(size), .. } => {
+                write!(f, "{} {} {} @ market", side.to_string().to_uppercase(), size, product_id)
+            }
+            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Funds(funds), .. } => {
+                write!(f, "{} {} funds of {} @ market", side.to_string().to_uppercase(), funds, product_id)
+            }
+            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Size(size), .. } => {
+                write!(f, "{} {} {} @ stop {}", side.to_string().to_uppercase(), size, product_id, price)
+            }
+            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Funds(funds), .. } => {
+                write!(f, "{} {} funds of {} @ stop {}", side.to_string().to_uppercase(), funds, product_id, price)
+            }
         }
     }
 }
@@ -195,7 +985,7 @@ impl Serialize for NewOrder {
         where S: serde::Serializer
     {
         match *self {
-            NewOrder::Limit { side, ref product_id, price, size } => {
+            NewOrder::Limit { side, ref product_id, price, size, post_only, client_oid } => {
                 // We create a struct representing the JSON
                 // and have Serialize auto derived for that
                 #[derive(Serialize)]
@@ -205,102 +995,156 @@ impl Serialize for NewOrder {
                     side: Side,
                     product_id: &'a str,
                     price: f64,
-                    size: f64
+                    size: f64,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    post_only: Option<bool>,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    client_oid: Option<Uuid>
                 }
                 LimitOrder {
                     t: "limit",
                     side: side,
                     product_id: product_id,
                     price: price,
-                    size: size
+                    size: size,
+                    post_only: if post_only { Some(true) } else { None },
+                    client_oid: client_oid
                 }.serialize(serializer)
             }
 
-            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Size(size) } => {
+            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Size(size), client_oid } => {
                 #[derive(Serialize)]
                 struct MarketOrder<'a> {
                     #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a str,
-                    size: f64
+                    size: f64,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    client_oid: Option<Uuid>
                 }
                 MarketOrder {
                     t: "market",
                     side: side,
                     product_id: product_id,
-                    size: size
+                    size: size,
+                    client_oid: client_oid
                 }.serialize(serializer)
             }
 
-            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Funds(funds) } => {
+            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Funds(funds), client_oid } => {
                 #[derive(Serialize)]
                 struct MarketOrder<'a> {
                     #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a str,
-                    funds: f64
+                    funds: f64,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    client_oid: Option<Uuid>
                 }
                 MarketOrder {
                     t: "market",
                     side: side,
                     product_id: product_id,
-                    funds: funds
+                    funds: funds,
+                    client_oid: client_oid
                 }.serialize(serializer)
             }
 
-            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Size(size) } => {
+            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Size(size), direction, client_oid } => {
                 #[derive(Serialize)]
                 struct StopOrder<'a> {
                     #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a str,
+                    stop: &'static str,
                     price: f64,
-                    size: f64
+                    size: f64,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    client_oid: Option<Uuid>
                 }
                 StopOrder {
                     t: "stop",
                     side: side,
                     product_id: product_id,
+                    stop: direction.as_str(),
                     price: price,
-                    size: size
+                    size: size,
+                    client_oid: client_oid
                 }.serialize(serializer)
             }
 
-            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Funds(funds) } => {
+            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Funds(funds), direction, client_oid } => {
                 #[derive(Serialize)]
                 struct StopOrder<'a> {
                     #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a str,
+                    stop: &'static str,
                     price: f64,
-                    funds: f64
+                    funds: f64,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    client_oid: Option<Uuid>
                 }
                 StopOrder {
                     t: "stop",
                     side: side,
                     product_id: product_id,
+                    stop: direction.as_str(),
                     price: price,
-                    funds: funds
+                    funds: funds,
+                    client_oid: client_oid
                 }.serialize(serializer)
             }
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct OpenOrder {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrderStatus {
+    Open,
+    Pending,
+    Active,
+    Done,
+    Settled
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OrderStatus::Open => "open",
+            OrderStatus::Pending => "pending",
+            OrderStatus::Active => "active",
+            OrderStatus::Done => "done",
+            OrderStatus::Settled => "settled"
+        }
+    }
+
+    // Whether the `/orders` endpoint accepts this as a `status` filter
+    pub fn is_queryable(&self) -> bool {
+        *self != OrderStatus::Settled
+    }
+}
+
+// GDAX leaves out the fill totals on an order that was only just
+// placed, so those default to zero rather than failing to parse
+#[derive(Deserialize, Debug)]
+pub struct OpenOrder {
     pub id: OrderId,
     pub size: f64,
     pub price: f64,
     pub product_id: String,
     pub status: String,
+    #[serde(default)]
     pub filled_size: f64,
+    #[serde(default)]
     pub executed_value: f64,
+    #[serde(default)]
     pub fill_fees: f64,
+    #[serde(default)]
     pub settled: bool,
     pub side: Side,
     pub created_at: DateTime<UTC>
@@ -311,52 +1155,757 @@ pub struct Order {
     pub id: OrderId,
     pub size: f64,
     pub price: f64,
+    #[serde(default)]
     pub done_reason: Option<String>,
     pub status: String,
+    #[serde(default)]
     pub settled: bool,
+    #[serde(default)]
     pub filled_size: f64,
+    #[serde(default)]
     pub executed_value: f64,
     pub product_id: String,
+    #[serde(default)]
     pub fill_fees: f64,
     pub side: Side,
     pub created_at: DateTime<UTC>,
+    #[serde(default)]
     pub done_at: Option<DateTime<UTC>>
 }
 
+impl From<Order> for OpenOrder {
+    fn from(order: Order) -> OpenOrder {
+        OpenOrder {
+            id: order.id,
+            size: order.size,
+            price: order.price,
+            product_id: order.product_id,
+            status: order.status,
+            filled_size: order.filled_size,
+            executed_value: order.executed_value,
+            fill_fees: order.fill_fees,
+            settled: order.settled,
+            side: order.side,
+            created_at: order.created_at
+        }
+    }
+}
+
+// The fields shared by `Order` and `OpenOrder`, so code handling
+// orders can take results from `get_order` and `get_orders` alike
+pub trait OrderLike {
+    fn id(&self) -> OrderId;
+    fn side(&self) -> Side;
+    fn price(&self) -> f64;
+    fn size(&self) -> f64;
+    fn filled_size(&self) -> f64;
+    fn status(&self) -> &str;
+    fn product_id(&self) -> &str;
+}
+
+impl OrderLike for OpenOrder {
+    fn id(&self) -> OrderId {
+        self.id
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn price(&self) -> f64 {
+        self.price
+    }
+
+    fn size(&self) -> f64 {
+        self.size
+    }
+
+    fn filled_size(&self) -> f64 {
+        self.filled_size
+    }
+
+    fn status(&self) -> &str {
+        &self.status
+    }
+
+    fn product_id(&self) -> &str {
+        &self.product_id
+    }
+}
+
+impl OrderLike for Order {
+    fn id(&self) -> OrderId {
+        self.id
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn price(&self) -> f64 {
+        self.price
+    }
+
+    fn size(&self) -> f64 {
+        self.size
+    }
+
+    fn filled_size(&self) -> f64 {
+        self.filled_size
+    }
+
+    fn status(&self) -> &str {
+        &self.status
+    }
+
+    fn product_id(&self) -> &str {
+        &self.product_id
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Liquidity {
+    Maker,
+    Taker
+}
+
+impl Serialize for Liquidity {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(match *self {
+            Liquidity::Maker => "M",
+            Liquidity::Taker => "T"
+        })
+    }
+}
+
+// We manually implement Deserialize for Liquidity here
+// because GDAX sends it as a single letter, `M` or `T`
+impl serde::Deserialize for Liquidity {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Liquidity, D::Error>
+        where D: serde::Deserializer {
+
+        struct LiquidityVisitor;
+        impl serde::de::Visitor for LiquidityVisitor {
+            type Value = Liquidity;
+
+            fn visit_str<E>(&mut self, v: &str) -> Result<Self::Value, E>
+                where E: serde::Error {
+                match v {
+                    "M" | "m" => Ok(Liquidity::Maker),
+                    "T" | "t" => Ok(Liquidity::Taker),
+                    _ => Err(E::invalid_value("liquidity must be either `M` or `T`"))
+                }
+            }
+        }
+        deserializer.deserialize(LiquidityVisitor)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct Fill {
+    pub trade_id: u64,
+    pub product_id: String,
+    pub price: f64,
+    pub size: f64,
+    pub order_id: OrderId,
+    pub created_at: DateTime<UTC>,
+    pub liquidity: Liquidity,
+    pub fee: f64,
+    pub settled: bool,
+    pub side: Side
+}
+
+impl Fill {
+    pub fn is_maker(&self) -> bool {
+        self.liquidity == Liquidity::Maker
+    }
+}
+
+impl Execution for Fill {
+    fn price(&self) -> f64 {
+        self.price
+    }
+
+    fn size(&self) -> f64 {
+        self.size
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn time(&self) -> DateTime<UTC> {
+        self.created_at
+    }
+}
+
+// Notional traded on one product, in its quote currency
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LiquidityVolume {
+    pub maker: f64,
+    pub taker: f64
+}
+
+impl LiquidityVolume {
+    pub fn total(&self) -> f64 {
+        self.maker + self.taker
+    }
+
+    // `None` before anything has traded
+    pub fn maker_share(&self) -> Option<f64> {
+        let total = self.total();
+        if total > 0. {
+            Some(self.maker / total)
+        } else {
+            None
+        }
+    }
+}
+
+// Maker and taker volume per product, from fills since `since`. Kept
+// per product since the volumes are in different quote currencies.
+#[derive(Debug)]
+pub struct VolumeBreakdown {
+    pub since: DateTime<UTC>,
+    pub by_product: HashMap<String, LiquidityVolume>
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReportFormat {
+    Pdf,
+    Csv
+}
+
+impl ReportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ReportFormat::Pdf => "pdf",
+            ReportFormat::Csv => "csv"
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum NewReport {
+    Fills {
+        product_id: String,
+        start_date: DateTime<UTC>,
+        end_date: DateTime<UTC>,
+        format: ReportFormat
+    },
+    Account {
+        account_id: Uuid,
+        start_date: DateTime<UTC>,
+        end_date: DateTime<UTC>,
+        format: ReportFormat
+    }
+}
+
+impl NewReport {
+    pub fn fills(product_id: &str, start_date: DateTime<UTC>, end_date: DateTime<UTC>) -> NewReport {
+        NewReport::Fills {
+            product_id: product_id.to_owned(),
+            start_date: start_date,
+            end_date: end_date,
+            format: ReportFormat::Pdf
+        }
+    }
+
+    pub fn account(account_id: Uuid, start_date: DateTime<UTC>, end_date: DateTime<UTC>) -> NewReport {
+        NewReport::Account {
+            account_id: account_id,
+            start_date: start_date,
+            end_date: end_date,
+            format: ReportFormat::Pdf
+        }
+    }
+
+    pub fn with_format(mut self, new_format: ReportFormat) -> NewReport {
+        match self {
+            NewReport::Fills { ref mut format, .. } |
+            NewReport::Account { ref mut format, .. } => *format = new_format
+        }
+        self
+    }
+}
+
+// The form GDAX documents for report dates, always in UTC with
+// millisecond precision, e.g. "2016-06-01T00:00:00.000Z"
+pub fn report_date(date: &DateTime<UTC>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+// We manually implement Serialize for NewReport since each
+// variant is its own report type and dates need a fixed format
+impl Serialize for NewReport {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: serde::Serializer
+    {
+        match *self {
+            NewReport::Fills { ref product_id, ref start_date, ref end_date, format } => {
+                #[derive(Serialize)]
+                struct FillsReport<'a> {
+                    #[serde(rename = "type")]
+                    t: &'static str,
+                    start_date: String,
+                    end_date: String,
+                    product_id: &'a str,
+                    format: &'static str
+                }
+                FillsReport {
+                    t: "fills",
+                    start_date: report_date(start_date),
+                    end_date: report_date(end_date),
+                    product_id: product_id,
+                    format: format.as_str()
+                }.serialize(serializer)
+            }
+
+            NewReport::Account { account_id, ref start_date, ref end_date, format } => {
+                #[derive(Serialize)]
+                struct AccountReport {
+                    #[serde(rename = "type")]
+                    t: &'static str,
+                    start_date: String,
+                    end_date: String,
+                    account_id: Uuid,
+                    format: &'static str
+                }
+                AccountReport {
+                    t: "account",
+                    start_date: report_date(start_date),
+                    end_date: report_date(end_date),
+                    account_id: account_id,
+                    format: format.as_str()
+                }.serialize(serializer)
+            }
+        }
+    }
+}
+
+// `status` moves from pending to creating to ready,
+// at which point `file_url` points at the finished report
+#[derive(Deserialize, Debug)]
+pub struct Report {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub report_type: String,
+    pub status: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<UTC>>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<UTC>>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<UTC>>,
+    #[serde(default)]
+    pub file_url: Option<String>
+}
+
+// Polls an order until it's done, starting at `poll_interval` and
+// multiplying the wait by `backoff_factor` after each poll up to
+// `max_interval`. A market order can use a short interval and wait
+// milliseconds, a limit far from the market a long one and wait hours.
+pub struct OrderWaiter<'a> {
+    client: &'a Client,
+    order_id: OrderId,
+    poll_interval: Duration,
+    max_interval: Duration,
+    backoff_factor: f64,
+    timeout: Option<Duration>
+}
+
+impl<'a> OrderWaiter<'a> {
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> OrderWaiter<'a> {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_max_interval(mut self, max_interval: Duration) -> OrderWaiter<'a> {
+        self.max_interval = max_interval;
+        self
+    }
+
+    pub fn with_backoff_factor(mut self, backoff_factor: f64) -> OrderWaiter<'a> {
+        self.backoff_factor = backoff_factor;
+        self
+    }
+
+    // Gives up with `Error::Timeout` once this long has passed
+    pub fn with_timeout(mut self, timeout: Duration) -> OrderWaiter<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Blocks until the order is done or rejected and returns it as last seen
+    pub fn wait(self) -> Result<Order, Error> {
+        let started = Instant::now();
+        let mut interval = self.poll_interval;
+
+        loop {
+            let order = self.client.get_order(self.order_id)?;
+            if order.status == "done" || order.status == "rejected" {
+                return Ok(order);
+            }
+
+            if let Some(timeout) = self.timeout {
+                let elapsed = started.elapsed();
+                if elapsed >= timeout {
+                    return Err(Error::Timeout);
+                }
+                if elapsed + interval > timeout {
+                    interval = timeout - elapsed;
+                }
+            }
+            thread::sleep(interval);
+
+            interval = scale(interval, self.backoff_factor);
+            if interval > self.max_interval {
+                interval = self.max_interval;
+            }
+        }
+    }
+}
+
+fn scale(duration: Duration, factor: f64) -> Duration {
+    let nanos = (duration.as_secs() as f64 * 1e9 + duration.subsec_nanos() as f64) * factor;
+    Duration::new((nanos / 1e9) as u64, (nanos % 1e9) as u32)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FundingStatus {
+    Outstanding,
+    Settled,
+    Rejected
+}
+
+impl FundingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FundingStatus::Outstanding => "outstanding",
+            FundingStatus::Settled => "settled",
+            FundingStatus::Rejected => "rejected"
+        }
+    }
+}
+
+// We manually implement Deserialize for FundingStatus here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl serde::Deserialize for FundingStatus {
+    fn deserialize<D>(deserializer: &mut D) -> Result<FundingStatus, D::Error>
+        where D: serde::Deserializer {
+
+        struct FundingStatusVisitor;
+        impl serde::de::Visitor for FundingStatusVisitor {
+            type Value = FundingStatus;
+
+            fn visit_str<E>(&mut self, v: &str) -> Result<Self::Value, E>
+                where E: serde::Error {
+                match &*v.to_lowercase() {
+                    "outstanding" => Ok(FundingStatus::Outstanding),
+                    "settled" => Ok(FundingStatus::Settled),
+                    "rejected" => Ok(FundingStatus::Rejected),
+                    _ => Err(E::invalid_value("funding status must be `outstanding`, `settled` or `rejected`"))
+                }
+            }
+        }
+        deserializer.deserialize(FundingStatusVisitor)
+    }
+}
+
+// Margin borrowed to fund an order, and how much of it has been paid back
+#[derive(Deserialize, Debug)]
+pub struct FundingRecord {
+    pub id: Uuid,
+    #[serde(default)]
+    pub order_id: Option<OrderId>,
+    pub amount: f64,
+    pub currency: String,
+    pub status: FundingStatus,
+    pub created_at: DateTime<UTC>,
+    #[serde(default)]
+    pub repaid_amount: f64
+}
+
+// How an account's hold compares with its individual holds
+// and with what its open orders should have locked up
+#[derive(Debug)]
+pub struct HoldReconciliation {
+    pub currency: String,
+    // `hold` as reported on the account
+    pub account_hold: f64,
+    // Sum of the account's individual holds
+    pub holds_total: f64,
+    // Remaining size of open sells, or value of open buys, in `currency`
+    pub expected_from_orders: f64,
+    // Order holds whose order isn't open any more
+    pub stale_holds: Vec<Uuid>,
+    // Open orders in `currency` with no hold against them
+    pub orders_without_hold: Vec<OrderId>,
+    // Order holds for a different amount than their order has left
+    pub mismatched_holds: Vec<HoldMismatch>
+}
+
+// An order hold that doesn't cover what's left of its order: the
+// remaining size of a sell, or its value at the limit price for a buy
+#[derive(Debug)]
+pub struct HoldMismatch {
+    pub hold_id: Uuid,
+    pub order_id: OrderId,
+    pub held: f64,
+    pub expected: f64
+}
+
+// Highest fee a buy's hold may include on top of the order's value
+const MAX_FEE_RATE: f64 = 0.0025;
+
+const HOLD_TOLERANCE: f64 = 1e-8;
+
+// An order can take a moment to show up in lookups after it lands, so a
+// lookup following a failed submission is retried before giving up
+const ORDER_LOOKUP_ATTEMPTS: u32 = 3;
+const ORDER_LOOKUP_DELAY_MS: u64 = 500;
+
+impl HoldReconciliation {
+    pub fn is_consistent(&self) -> bool {
+        (self.account_hold - self.holds_total).abs() < HOLD_TOLERANCE &&
+            self.stale_holds.is_empty() &&
+            self.orders_without_hold.is_empty() &&
+            self.mismatched_holds.is_empty()
+    }
+}
+
+// How the exchange's open orders differ from the ones expected to be open
+#[derive(Debug)]
+pub struct Reconciliation {
+    // Expected but not open, most likely filled or cancelled
+    pub missing: Vec<OrderId>,
+    // Open but not expected
+    pub unexpected: Vec<OpenOrder>
+}
+
+impl Reconciliation {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct CancelSummary {
+    pub requested: usize,
+    pub cancelled: Vec<OrderId>,
+    pub remaining: usize
+}
+
+impl fmt::Display for OpenOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} @ {} ({}, filled {})",
+               self.side.to_string().to_uppercase(),
+               self.size,
+               self.product_id,
+               self.price,
+               self.status,
+               self.filled_size)
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} @ {} ({}, filled {})",
+               self.side.to_string().to_uppercase(),
+               self.size,
+               self.product_id,
+               self.price,
+               self.status,
+               self.filled_size)
+    }
+}
+
 impl Client {
     pub fn new(key: &str, secret: &str, passphrase: &str) -> Client {
+        Client::from_config(key, secret, passphrase, Config::new(), Arc::new(HttpClient::new()))
+    }
+
+    // Used by `ClientBuilder`. The wrapped public client gets the same
+    // configuration and shares the connection pool.
+    pub fn from_config(key: &str,
+                       secret: &str,
+                       passphrase: &str,
+                       config: Config,
+                       http_client: Arc<HttpClient>)
+        -> Client {
+
         Client {
-            public_client: super::public::Client::new(),
-            http_client: HttpClient::new(),
+            public_client: super::public::Client::from_config(config.clone(), http_client.clone()),
+            http_client: http_client,
             key: key.to_owned(),
             secret: secret.to_owned(),
-            passphrase: passphrase.to_owned()
+            passphrase: passphrase.to_owned(),
+            timestamper: Arc::new(Timestamper::new()),
+            rate_limiter: Arc::new(RateLimiter::new()),
+            cancel_dedup: None,
+            order_retries: None,
+            default_headers: Headers::new(),
+            config: Arc::new(config),
+            journal: None,
+            journaled_fills: Arc::new(Mutex::new(HashMap::new())),
+            balance_check: false
         }
     }
 
-    fn signature(&self, path: &str, body: &str, timestamp: &str, method: &str)
-        -> Result<String, Error> {
+    // Builds a client from the `CB_KEY`, `CB_SECRET` and `CB_PASSPHRASE`
+    // environment variables, checking up front that the secret decodes
+    pub fn from_env() -> Result<Client, Error> {
+        fn var(name: &'static str) -> Result<String, Error> {
+            env::var(name).map_err(|_| Error::MissingCredential(name))
+        }
 
-        let key = base64::decode(&self.secret)?;
-        let what = format!("{}{}{}{}",
-                           timestamp,
-                           method.to_uppercase(),
-                           path,
-                           body);
+        let key = var(KEY_VAR)?;
+        let secret = var(SECRET_VAR)?;
+        let passphrase = var(PASSPHRASE_VAR)?;
+        check_secret(&secret)?;
 
-        let mut hmac = Hmac::new(Sha256::new(), &key);
-        hmac.input(what.as_bytes());
+        Ok(Client::new(&key, &secret, &passphrase))
+    }
 
-        Ok(base64::encode(hmac.result().code()))
+    // Extra headers sent along with every request, public ones included.
+    // They can't replace the `CB-ACCESS-*` headers used for signing.
+    pub fn with_default_headers(mut self, headers: Headers) -> Client {
+        self.public_client = self.public_client.clone().with_default_headers(headers.clone());
+        self.default_headers = headers;
+        self
+    }
+
+    // Records every order posted or cancelled, mass cancels included,
+    // and every new fill fetched, each only once however many times
+    // it comes back
+    pub fn with_journal<J>(mut self, journal: J) -> Client
+        where J: OrderJournal + 'static
+    {
+        self.journal = Some(Arc::new(journal));
+        self
+    }
+
+    // Flushes the order journal, if there is one. Also attempted when
+    // the last clone of the client is dropped, but errors are only
+    // logged then, so call this before exiting to know it succeeded.
+    pub fn flush(&self) -> Result<(), Error> {
+        match self.journal {
+            Some(ref journal) => journal.flush(),
+            None => Ok(())
+        }
+    }
+
+    fn record<F>(&self, f: F)
+        where F: FnOnce(&OrderJournal) -> Result<(), Error>
+    {
+        if let Some(ref journal) = self.journal {
+            if let Err(err) = f(&**journal) {
+                warn!("unable to write to the order journal: {:?}", err);
+            }
+        }
+    }
+
+    // Sends `Connection: close` on every request, public ones included,
+    // see `PublicClient::with_connection_close`
+    pub fn with_connection_close(mut self) -> Client {
+        self.public_client = self.public_client.clone().with_connection_close();
+        Arc::make_mut(&mut self.config).connection_close = true;
+        self
+    }
+
+    // Has `post_order` check the order against the available balance
+    // first, failing with `Error::InsufficientFunds` without a round trip
+    // to place it. This costs an extra request per order and is only a
+    // snapshot: another order or a withdrawal landing between the check
+    // and the post can still leave the order short, and fees aren't
+    // counted. Market buys given in size have no known cost and pass.
+    pub fn with_balance_check(mut self) -> Client {
+        self.balance_check = true;
+        self
+    }
+
+    fn check_balance(&self, order: &NewOrder) -> Result<(), Error> {
+        let (side, product_id, required) = match *order {
+            NewOrder::Limit { side, ref product_id, price, size, .. } |
+            NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Size(size), .. } => {
+                match side {
+                    Side::Buy => (side, product_id, size * price),
+                    Side::Sell => (side, product_id, size)
+                }
+            }
+            NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Funds(funds), .. } |
+            NewOrder::Stop { side: side @ Side::Buy, ref product_id, size_or_funds: SizeOrFunds::Funds(funds), .. } => {
+                (side, product_id, funds)
+            }
+            // Funds are in the quote currency but a sell draws on the
+            // base, so estimate the size from the stop price
+            NewOrder::Stop { side: Side::Sell, ref product_id, price, size_or_funds: SizeOrFunds::Funds(funds), .. } => {
+                if price <= 0. {
+                    return Err(Error::InvalidArgument(format!("stop price {} must be positive", price)));
+                }
+                (Side::Sell, product_id, funds / price)
+            }
+            NewOrder::Market { side: Side::Sell, ref product_id, size_or_funds: SizeOrFunds::Size(size), .. } => {
+                (Side::Sell, product_id, size)
+            }
+            NewOrder::Market { side: Side::Buy, size_or_funds: SizeOrFunds::Size(_), .. } => return Ok(())
+        };
+
+        let product_id = ProductId::new(product_id)?;
+        let currency = match side {
+            Side::Buy => product_id.quote(),
+            Side::Sell => product_id.base()
+        };
+
+        let accounts = self.get_accounts()?;
+        let available = accounts.by_currency(currency).map_or(0., |account| account.available);
+        if required > available {
+            return Err(Error::InsufficientFunds {
+                currency: currency.to_owned(),
+                required: required,
+                available: available
+            });
+        }
+        Ok(())
+    }
+
+    pub fn with_cancel_dedup(mut self, window: Duration) -> Client {
+        self.cancel_dedup = Some(Arc::new(CancelDedup::new(window)));
+        self
+    }
+
+    // Makes `post_order` safer to retry: every order gets a `client_oid`
+    // (unless it already has one) and a request that fails in transit is
+    // resubmitted, up to `retries` times, only after a few spaced out
+    // lookups by that `client_oid` come back empty. That's a good sign the
+    // earlier attempt never landed but not proof, so once the retries run
+    // out this returns `Error::OrderOutcomeUnknown` rather than the
+    // connection error.
+    pub fn with_idempotent_orders(mut self, retries: u32) -> Client {
+        self.order_retries = Some(retries);
+        self
+    }
+
+    fn signature(&self, path: &str, body: &str, timestamp: &str, method: &str)
+        -> Result<String, Error> {
+
+        sign(&self.secret, timestamp, method, path, body)
     }
 
     fn get_headers(&self, path: &str, body: &str, method: &str) -> Result<Headers, Error> {
-        let timestamp = get_time().sec.to_string();
+        let timestamp = self.timestamper.now();
         let signature = self.signature(path, body, &timestamp, method)?;
 
         let mut headers = Headers::new();
         headers.set(Accept(vec![qitem(Mime(TopLevel::Application, SubLevel::Json, vec![]))]));
-        headers.set(UserAgent("rust-gdax-client/0.1.0".to_owned()));
+        headers.set(UserAgent(self.config.user_agent.clone()));
+        if self.config.connection_close {
+            headers.set(Connection::close());
+        }
+        headers.extend(self.default_headers.iter());
         headers.set_raw("CB-ACCESS-KEY", vec![self.key.clone().into_bytes()]);
         headers.set_raw("CB-ACCESS-SIGN", vec![signature.into_bytes()]);
         headers.set_raw("CB-ACCESS-PASSPHRASE", vec![self.passphrase.clone().into_bytes()]);
@@ -365,114 +1914,684 @@ impl Client {
         Ok(headers)
     }
 
-    fn get_and_decode<T>(&self, path: &str) -> Result<T, Error>
+    pub fn rate_limit_status(&self) -> Option<RateLimitStatus> {
+        self.rate_limiter.status()
+    }
+
+    // Only GETs are retried, anything else could take effect twice
+    fn request_and_decode<T>(&self, method: Method, path: &str, body: &str)
+        -> Result<(T, Headers), Error>
         where T: Deserialize
     {
-        let headers = self.get_headers(path, "", "GET")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        let mut res = self.http_client.get(&url)
-                                      .headers(headers)
-                                      .send()?;
+        let retries = if method == Method::Get { self.config.retries } else { 0 };
 
-        if !res.status.is_success() {
-            return Err(Error::Api(de::from_reader(&mut res)?));
+        let mut attempts = 0;
+        loop {
+            match self.try_request_and_decode(method.clone(), path, body, self.config.rate_limit) {
+                Err(Error::Connection(err)) => {
+                    if attempts == retries {
+                        return Err(Error::Connection(err));
+                    }
+                    attempts += 1;
+                    warn!("retrying {} {} after {} ({} of {})", method, path, err, attempts, retries);
+                }
+                result => return result
+            }
         }
-
-        Ok(de::from_reader(&mut res)?)
     }
 
-    fn post_and_decode<T>(&self, path: &str, body: &str) -> Result<T, Error>
+    fn try_request_and_decode<T>(&self, method: Method, path: &str, body: &str, rate_limit: bool)
+        -> Result<(T, Headers), Error>
         where T: Deserialize
     {
-        let headers = self.get_headers(path, body, "POST")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        let mut res = self.http_client.post(&url)
-                                      .headers(headers)
-                                      .header(ContentType::json())
-                                      .body(body)
-                                      .send()?;
+        // Wait before signing so a long throttle can't push the
+        // timestamp outside the window GDAX accepts it in
+        if rate_limit {
+            self.rate_limiter.wait();
+        }
+
+        let headers = self.get_headers(path, body, &method.to_string())?;
+        let url = format!("{}{}", self.config.base_url, path);
+
+        let mut req = self.http_client.request(method, &url)
+                                      .headers(headers);
+        if !body.is_empty() {
+            req = req.header(ContentType::json())
+                     .body(body);
+        }
+        let mut res = req.send()?;
+
+        self.rate_limiter.update(&res.headers);
 
         if !res.status.is_success() {
-            return Err(Error::Api(de::from_reader(&mut res)?));
+            return Err(super::api_error(&mut res));
         }
 
-        Ok(de::from_reader(&mut res)?)
+        let decoded = de::from_reader(&mut res)?;
+        Ok((decoded, res.headers.clone()))
     }
 
-    fn delete_and_decode<T>(&self, path: &str) -> Result<T, Error>
+    fn get_page<T>(&self, path: &str) -> Result<Page<T>, Error>
         where T: Deserialize
     {
-        let headers = self.get_headers(path, "", "DELETE")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        let mut res = self.http_client.delete(&url)
-                                      .headers(headers)
-                                      .send()?;
+        let (items, headers) = self.request_and_decode(Method::Get, path, "")?;
+        Ok(Page::from_headers(items, &headers))
+    }
 
-        if !res.status.is_success() {
-            return Err(Error::Api(de::from_reader(&mut res)?));
-        }
+    fn get_and_decode<T>(&self, path: &str) -> Result<T, Error>
+        where T: Deserialize
+    {
+        self.request_and_decode(Method::Get, path, "").map(|(decoded, _)| decoded)
+    }
 
-        Ok(de::from_reader(&mut res)?)
+    fn post_and_decode<T>(&self, path: &str, body: &str) -> Result<T, Error>
+        where T: Deserialize
+    {
+        self.request_and_decode(Method::Post, path, body).map(|(decoded, _)| decoded)
+    }
+
+    fn delete_and_decode<T>(&self, path: &str) -> Result<T, Error>
+        where T: Deserialize
+    {
+        self.request_and_decode(Method::Delete, path, "").map(|(decoded, _)| decoded)
+    }
+
+    pub fn get_raw(&self, path: &str) -> Result<Value, Error> {
+        self.get_and_decode(path)
     }
 
     pub fn get_accounts(&self) -> Result<Vec<Account>, Error> {
         self.get_and_decode("/accounts")
     }
 
+    // Only the accounts with something in them or on hold
+    pub fn get_nonzero_accounts(&self) -> Result<Vec<Account>, Error> {
+        Ok(self.get_accounts()?
+               .into_iter()
+               .filter(|account| account.balance != 0. || account.hold != 0.)
+               .collect())
+    }
+
     pub fn get_account(&self, id: Uuid) -> Result<Account, Error> {
         self.get_and_decode(&format!("/accounts/{}", id))
     }
 
+    // Fetches the accounts a few at a time, returning
+    // the results in the same order as `ids`
+    pub fn get_accounts_by_ids(&self, ids: &[Uuid]) -> Vec<(Uuid, Result<Account, Error>)> {
+        let results = super::fan_out(self, ids.to_vec(), |client, id| client.get_account(id));
+        ids.iter().cloned().zip(results).collect()
+    }
+
+    pub fn account_changes(&self, poll_interval: Duration) -> AccountChanges {
+        AccountChanges {
+            client: self,
+            poll_interval: poll_interval,
+            last: None
+        }
+    }
+
     pub fn get_account_history(&self, id: Uuid) -> Result<Ledger, Error> {
-        self.get_and_decode(&format!("/accounts/{}/ledger", id))
+        self.get_account_history_page(id, None).map(|page| page.items)
+    }
+
+    pub fn get_account_history_page(&self, id: Uuid, cursor: Option<&Cursor>)
+        -> Result<Page<LedgerEntry>, Error> {
+
+        self.get_page(&super::with_query(&format!("/accounts/{}/ledger", id), vec![], cursor))
+    }
+
+    pub fn get_account_history_stream(&self, id: Uuid) -> PaginatedStream<LedgerEntry> {
+        PaginatedStream::new(move |cursor| self.get_account_history_page(id, cursor))
     }
 
     pub fn get_account_holds(&self, id: Uuid) -> Result<Vec<Hold>, Error> {
-        self.get_and_decode(&format!("/accounts/{}/holds", id))
+        self.get_account_holds_page(id, None).map(|page| page.items)
+    }
+
+    pub fn get_account_holds_page(&self, id: Uuid, cursor: Option<&Cursor>)
+        -> Result<Page<Hold>, Error> {
+
+        self.get_page(&super::with_query(&format!("/accounts/{}/holds", id), vec![], cursor))
+    }
+
+    pub fn get_account_holds_stream(&self, id: Uuid) -> PaginatedStream<Hold> {
+        PaginatedStream::new(move |cursor| self.get_account_holds_page(id, cursor))
+    }
+
+    pub fn get_account_transfers(&self, id: Uuid) -> Result<Vec<Transfer>, Error> {
+        self.get_account_transfers_page(id, None).map(|page| page.items)
+    }
+
+    pub fn get_account_transfers_page(&self, id: Uuid, cursor: Option<&Cursor>)
+        -> Result<Page<Transfer>, Error> {
+
+        self.get_page(&super::with_query(&format!("/accounts/{}/transfers", id), vec![], cursor))
+    }
+
+    pub fn get_account_transfers_stream(&self, id: Uuid) -> PaginatedStream<Transfer> {
+        PaginatedStream::new(move |cursor| self.get_account_transfers_page(id, cursor))
     }
 
     pub fn post_order(&self, order: &NewOrder) -> Result<OrderId, Error> {
+        order.check()?;
+        if self.balance_check {
+            self.check_balance(order)?;
+        }
+
+        let retries = match self.order_retries {
+            Some(retries) => retries,
+            None => return self.submit_order(order)
+        };
+
+        let (order, client_oid) = match order.client_oid() {
+            Some(client_oid) => (order.clone(), client_oid),
+            None => {
+                let client_oid = Uuid::new_v4();
+                (order.clone().with_client_oid(client_oid), client_oid)
+            }
+        };
+
+        let mut attempts = 0;
+        loop {
+            match self.submit_order(&order) {
+                Err(Error::Connection(err)) => {
+                    // The order may well have landed even though the
+                    // response never made it back to us, and it can take
+                    // a moment to show up, so look for it a few times.
+                    if let Some(existing) = self.find_order_by_client_oid(client_oid)? {
+                        return Ok(existing);
+                    }
+                    if attempts == retries {
+                        warn!("order {} failed in transit: {}", client_oid, err);
+                        return Err(Error::OrderOutcomeUnknown(client_oid));
+                    }
+                    attempts += 1;
+                    warn!("retrying order {} ({} of {})", client_oid, attempts, retries);
+                }
+                result => return result
+            }
+        }
+    }
+
+    fn find_order_by_client_oid(&self, client_oid: Uuid) -> Result<Option<OrderId>, Error> {
+        for lookup in 0..ORDER_LOOKUP_ATTEMPTS {
+            if lookup > 0 {
+                thread::sleep(Duration::from_millis(ORDER_LOOKUP_DELAY_MS));
+            }
+            match self.get_order_by_client_oid(client_oid) {
+                Ok(existing) => return Ok(Some(existing.id)),
+                Err(Error::Api(ref err)) if err.is_not_found() => {}
+                Err(err) => return Err(err)
+            }
+        }
+        Ok(None)
+    }
+
+    fn submit_order(&self, order: &NewOrder) -> Result<OrderId, Error> {
         #[derive(Deserialize)]
-        struct NewOrderResult { id: OrderId }
+        struct NewOrderResult {
+            id: OrderId,
+            #[serde(default)]
+            status: Option<String>,
+            #[serde(default)]
+            reject_reason: Option<String>
+        }
+
+        // A post only order that would cross comes back either as an
+        // error or as an order that was rejected straight away. Other
+        // orders can be refused with "Post only mode" while the product
+        // only accepts post only orders, which is a different problem.
+        let post_only = match *order {
+            NewOrder::Limit { post_only, .. } => post_only,
+            _ => false
+        };
+        let is_post_only = |reason: &str| {
+            let reason = reason.to_lowercase();
+            post_only && reason.contains("post only") && !reason.contains("post only mode")
+        };
 
         let body = ser::to_string(order)?;
-        Ok(self.post_and_decode::<NewOrderResult>("/orders", &body)?.id)
+        let result = match self.post_and_decode::<NewOrderResult>("/orders", &body) {
+            Ok(NewOrderResult { status: Some(ref status), reject_reason: Some(ref reason), .. })
+                if status == "rejected" && is_post_only(reason) => Err(Error::PostOnlyWouldCross),
+            Ok(result) => Ok(result.id),
+            Err(Error::Api(ref err)) if is_post_only(err.message()) => Err(Error::PostOnlyWouldCross),
+            Err(err) => Err(err)
+        };
+        self.record(|journal| journal.order_posted(order, &result));
+        result
+    }
+
+    // Requotes a product by placing `new_orders` first and only then
+    // cancelling the orders that were open beforehand, so there's never
+    // a moment without quotes. If placing an order fails its error is
+    // returned straight away: orders placed so far stay live and the old
+    // quotes are left alone. A cancel rejected because the old order
+    // already filled or was cancelled is ignored, any other cancel error
+    // is returned once every stale order has been attempted.
+    pub fn replace_quotes(&self, product_id: &str, new_orders: &[NewOrder]) -> Result<Vec<OrderId>, Error> {
+        let stale = self.all_open_orders()?
+                        .into_iter()
+                        .filter(|order| order.product_id == product_id)
+                        .map(|order| order.id)
+                        .collect::<Vec<_>>();
+
+        let mut placed = Vec::new();
+        for order in new_orders {
+            placed.push(self.post_order(order)?);
+        }
+
+        let mut error = None;
+        for order_id in stale {
+            match self.cancel_order(order_id) {
+                Ok(_) => {}
+                Err(Error::Api(ref err)) if err.is_not_found() || err.is_order_done() => {}
+                Err(err) => {
+                    if error.is_none() {
+                        error = Some(err);
+                    }
+                }
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(placed)
+        }
+    }
+
+    pub fn post_orders(&self, orders: &[NewOrder], mode: FailureMode) -> Vec<Result<OrderId, Error>> {
+        mode.run(orders, |order| self.post_order(order))
+    }
+
+    // A naive TWAP: splits `base` into `slices` child orders on the
+    // product's increments and posts one every `interval`. Nothing is
+    // posted if a slice would break the product's trading rules. After
+    // that every slice is submitted regardless of how earlier ones fared,
+    // and nothing is cancelled mid-flight.
+    pub fn twap_execute(&self, base: &NewOrder, product: &Product, slices: u32, interval: Duration)
+        -> Result<Vec<Result<OrderId, Error>>, Error> {
+
+        if slices == 0 {
+            return Ok(Vec::new());
+        }
+
+        let children = base.split(product, slices)?;
+        Ok(children.iter().enumerate().map(|(slice, child)| {
+            if slice > 0 {
+                thread::sleep(interval);
+            }
+            self.post_order(child)
+        }).collect())
     }
 
     pub fn cancel_order(&self, order_id: OrderId) -> Result<OrderId, Error> {
-        Ok(self.delete_and_decode::<Vec<OrderId>>(&format!("/orders/{}", order_id))?[0])
+        let previous = match self.cancel_dedup {
+            Some(ref dedup) => dedup.begin(order_id),
+            None => None
+        };
+        if let Some(CancelState::Cancelled(_)) = previous {
+            return Ok(order_id);
+        }
+
+        let result = match self.delete_and_decode::<Vec<OrderId>>(&format!("/orders/{}", order_id)) {
+            Ok(cancelled) => Ok(cancelled[0]),
+            // The earlier DELETE that failed in transit did land
+            Err(Error::Api(ref err)) if previous.is_some() && (err.is_not_found() || err.is_order_done()) => {
+                Ok(order_id)
+            }
+            Err(err) => Err(err)
+        };
+        self.record(|journal| journal.order_cancelled(order_id, &result));
+
+        if let Some(ref dedup) = self.cancel_dedup {
+            dedup.finish(order_id, &result);
+        }
+
+        result
+    }
+
+    pub fn cancel_orders(&self, order_ids: &[OrderId], mode: FailureMode) -> Vec<Result<OrderId, Error>> {
+        mode.run(order_ids, |&order_id| self.cancel_order(order_id))
     }
 
     pub fn cancel_all_orders(&self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error> {
-        if let Some(product_id) = product_id {
+        let result = if let Some(product_id) = product_id {
             self.delete_and_decode(&format!("/orders?product_id={}", product_id))
         } else {
             self.delete_and_decode("/orders")
+        };
+        self.record(|journal| journal.all_orders_cancelled(product_id, &result));
+        result
+    }
+
+    // Like `cancel_all_orders` but goes out straight away even when the
+    // rate limiter would hold it back. With little budget left GDAX may
+    // well answer 429 Too Many Requests, in which case this returns an
+    // `Error::Api` and it's up to the caller to try again.
+    pub fn cancel_all_orders_urgent(&self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error> {
+        let path = match product_id {
+            Some(product_id) => format!("/orders?product_id={}", product_id),
+            None => "/orders".to_owned()
+        };
+        let result = self.try_request_and_decode(Method::Delete, &path, "", false).map(|(decoded, _)| decoded);
+        self.record(|journal| journal.all_orders_cancelled(product_id, &result));
+        result
+    }
+
+    // Cancels all orders, like `cancel_all_orders`, then checks the open
+    // orders again to count how many are still around afterwards
+    pub fn cancel_all_orders_summary(&self, product_id: Option<&str>) -> Result<CancelSummary, Error> {
+        let open_orders = |orders: Vec<OpenOrder>| {
+            orders.into_iter()
+                  .filter(|order| product_id.map_or(true, |product_id| order.product_id == product_id))
+                  .count()
+        };
+
+        let requested = open_orders(self.all_open_orders()?);
+        let cancelled = self.cancel_all_orders(product_id)?;
+        let remaining = open_orders(self.all_open_orders()?);
+
+        Ok(CancelSummary {
+            requested: requested,
+            cancelled: cancelled,
+            remaining: remaining
+        })
+    }
+
+    pub fn get_orders_with_status(&self, statuses: &[OrderStatus]) -> Result<Vec<OpenOrder>, Error> {
+        self.get_orders_page(statuses, None).map(|page| page.items)
+    }
+
+    pub fn get_orders_page(&self, statuses: &[OrderStatus], cursor: Option<&Cursor>)
+        -> Result<Page<OpenOrder>, Error> {
+
+        if let Some(&status) = statuses.iter().find(|status| !status.is_queryable()) {
+            return Err(Error::InvalidOrderStatus(status));
         }
+
+        let query = statuses.iter()
+                            .map(|status| format!("status={}", status.as_str()))
+                            .collect();
+        self.get_page(&super::with_query("/orders", query, cursor))
     }
 
-    pub fn get_orders_with_status(&self,
-                                  open: bool,
-                                  pending: bool,
-                                  active: bool)
-        -> Result<Vec<OpenOrder>, Error>
-    {
-        let status = [open, pending, active].iter()
-                                            .zip(["status=open", "status=pending", "status=active"].iter())
-                                            .filter(|&(&flag, _)| flag)
-                                            .map(|(_, &s)| s)
-                                            .collect::<Vec<_>>()
-                                            .join("&");
-        self.get_and_decode(&format!("/orders?{}", status))
+    pub fn get_orders_stream<'a>(&'a self, statuses: &'a [OrderStatus]) -> PaginatedStream<'a, OpenOrder> {
+        PaginatedStream::new(move |cursor| self.get_orders_page(statuses, cursor))
     }
 
     pub fn get_orders(&self) -> Result<Vec<OpenOrder>, Error> {
-        self.get_orders_with_status(true, true, true)
+        self.get_orders_with_status(&[OrderStatus::Open, OrderStatus::Pending, OrderStatus::Active])
+    }
+
+    // Every open, pending or active order, following the pagination
+    // where `get_orders` stops at the first page
+    pub fn all_open_orders(&self) -> Result<Vec<OpenOrder>, Error> {
+        let statuses = [OrderStatus::Open, OrderStatus::Pending, OrderStatus::Active];
+        let orders = self.get_orders_stream(&statuses).collect();
+        orders
+    }
+
+    // Value still resting in the book across open orders, for one product
+    // or all of them, keyed by quote currency since the products can be
+    // priced in different currencies. Partially filled orders only count
+    // what's left unfilled.
+    pub fn total_open_order_value(&self, product_id: Option<&str>) -> Result<HashMap<String, f64>, Error> {
+        let mut by_quote = HashMap::new();
+        for order in self.all_open_orders()? {
+            if product_id.map_or(false, |product_id| order.product_id != product_id) {
+                continue;
+            }
+            let quote = ProductId::new(&order.product_id)?.quote().to_string();
+            *by_quote.entry(quote).or_insert(0.) += order.price * (order.size - order.filled_size).max(0.);
+        }
+        Ok(by_quote)
+    }
+
+    // Cross-checks an account's hold against its holds and open orders.
+    // The expected amount leaves out fees, so a buy's hold normally
+    // comes out a little above it.
+    pub fn verify_account_holds(&self, account: &Account) -> Result<HoldReconciliation, Error> {
+        let holds = self.get_account_holds_stream(account.id).collect::<Result<Vec<_>, _>>()?;
+
+        let orders = self.all_open_orders()?.into_iter().filter(|order| {
+            ProductId::new(&order.product_id).ok().map_or(false, |product_id| {
+                match order.side {
+                    Side::Buy => product_id.quote() == account.currency,
+                    Side::Sell => product_id.base() == account.currency
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        let expected_hold = |order: &OpenOrder| {
+            let remaining = order.size - order.filled_size;
+            match order.side {
+                Side::Buy => remaining * order.price,
+                Side::Sell => remaining
+            }
+        };
+        let expected_from_orders = orders.iter().fold(0., |total, order| total + expected_hold(order));
+
+        // Buys hold their fees as well, sells only the size
+        let mismatched_holds = holds.iter().filter_map(|hold| {
+            let order = match orders.iter().find(|order| hold.hold_type == HoldType::Order && order.id == hold.ref_id) {
+                Some(order) => order,
+                None => return None
+            };
+            let expected = expected_hold(order);
+            let most = match order.side {
+                Side::Buy => expected * (1. + MAX_FEE_RATE),
+                Side::Sell => expected
+            };
+
+            if hold.amount < expected - HOLD_TOLERANCE || hold.amount > most + HOLD_TOLERANCE {
+                Some(HoldMismatch {
+                    hold_id: hold.id,
+                    order_id: order.id,
+                    held: hold.amount,
+                    expected: expected
+                })
+            } else {
+                None
+            }
+        }).collect();
+
+        let order_ids = orders.iter().map(|order| order.id).collect::<HashSet<_>>();
+        let held_ids = holds.iter()
+                            .filter(|hold| hold.hold_type == HoldType::Order)
+                            .map(|hold| hold.ref_id)
+                            .collect::<HashSet<_>>();
+
+        Ok(HoldReconciliation {
+            currency: account.currency.clone(),
+            account_hold: account.hold,
+            holds_total: holds.iter().fold(0., |total, hold| total + hold.amount),
+            expected_from_orders: expected_from_orders,
+            stale_holds: holds.iter()
+                              .filter(|hold| hold.hold_type == HoldType::Order && !order_ids.contains(&hold.ref_id))
+                              .map(|hold| hold.id)
+                              .collect(),
+            orders_without_hold: orders.iter()
+                                       .map(|order| order.id)
+                                       .filter(|id| !held_ids.contains(id))
+                                       .collect(),
+            mismatched_holds: mismatched_holds
+        })
+    }
+
+    // Compares `expected` against every open order on the exchange
+    pub fn reconcile_orders(&self, expected: &[OrderId]) -> Result<Reconciliation, Error> {
+        let open = self.all_open_orders()?;
+        let open_ids = open.iter().map(|order| order.id).collect::<HashSet<_>>();
+        let expected_ids = expected.iter().cloned().collect::<HashSet<_>>();
+
+        Ok(Reconciliation {
+            missing: expected.iter().cloned().filter(|id| !open_ids.contains(id)).collect(),
+            unexpected: open.into_iter().filter(|order| !expected_ids.contains(&order.id)).collect()
+        })
     }
 
     pub fn get_order(&self, order_id: OrderId) -> Result<Order, Error> {
         self.get_and_decode(&format!("/orders/{}", order_id))
     }
+
+    pub fn wait_for(&self, order_id: OrderId) -> OrderWaiter {
+        OrderWaiter {
+            client: self,
+            order_id: order_id,
+            poll_interval: Duration::from_millis(250),
+            max_interval: Duration::from_secs(10),
+            backoff_factor: 2.,
+            timeout: None
+        }
+    }
+
+    pub fn get_order_by_client_oid(&self, client_oid: Uuid) -> Result<Order, Error> {
+        self.get_and_decode(&format!("/orders/client:{}", client_oid))
+    }
+
+    pub fn get_funding_history(&self, status: Option<FundingStatus>) -> Result<Vec<FundingRecord>, Error> {
+        self.get_funding_history_page(status, None).map(|page| page.items)
+    }
+
+    pub fn get_funding_history_page(&self, status: Option<FundingStatus>, cursor: Option<&Cursor>)
+        -> Result<Page<FundingRecord>, Error> {
+
+        let query = status.into_iter().map(|status| format!("status={}", status.as_str())).collect();
+        self.get_page(&super::with_query("/funding", query, cursor))
+    }
+
+    pub fn get_funding_history_stream(&self, status: Option<FundingStatus>) -> PaginatedStream<FundingRecord> {
+        PaginatedStream::new(move |cursor| self.get_funding_history_page(status, cursor))
+    }
+
+    pub fn create_report(&self, report: &NewReport) -> Result<Report, Error> {
+        let body = ser::to_string(report)?;
+        self.post_and_decode("/reports", &body)
+    }
+
+    pub fn get_report(&self, id: Uuid) -> Result<Report, Error> {
+        self.get_and_decode(&format!("/reports/{}", id))
+    }
+
+    pub fn get_fills(&self, order_id: Option<OrderId>, product_id: Option<&str>)
+        -> Result<Vec<Fill>, Error> {
+
+        self.get_fills_page(order_id, product_id, None).map(|page| page.items)
+    }
+
+    pub fn get_fills_page(&self,
+                          order_id: Option<OrderId>,
+                          product_id: Option<&str>,
+                          cursor: Option<&Cursor>)
+        -> Result<Page<Fill>, Error> {
+
+        let mut query = Vec::new();
+        if let Some(order_id) = order_id {
+            query.push(format!("order_id={}", order_id));
+        }
+        if let Some(product_id) = product_id {
+            query.push(format!("product_id={}", product_id));
+        }
+        let page = self.get_page(&super::with_query("/fills", query, cursor))?;
+
+        // Fills get fetched over and over while paging back through the
+        // history, so only those newer than the newest fill journaled for
+        // their product go in. Older ones turning up later are taken to be
+        // journaled already or to predate the journal.
+        if self.journal.is_some() {
+            let unseen = {
+                let mut journaled = self.journaled_fills.lock().unwrap();
+                let unseen = page.items.iter()
+                                 .filter(|fill| journaled.get(&fill.product_id).map_or(true, |&newest| {
+                                     fill.trade_id > newest
+                                 }))
+                                 .cloned()
+                                 .collect::<Vec<_>>();
+                for fill in &unseen {
+                    let newest = journaled.entry(fill.product_id.clone()).or_insert(fill.trade_id);
+                    if fill.trade_id > *newest {
+                        *newest = fill.trade_id;
+                    }
+                }
+                unseen
+            };
+            if !unseen.is_empty() {
+                self.record(|journal| journal.fills_observed(&unseen));
+            }
+        }
+        Ok(page)
+    }
+
+    pub fn get_fills_stream<'a>(&'a self, order_id: Option<OrderId>, product_id: Option<&'a str>)
+        -> PaginatedStream<'a, Fill> {
+
+        PaginatedStream::new(move |cursor| self.get_fills_page(order_id, product_id, cursor))
+    }
+
+    // Counts fills by paging through every one of them, which costs a
+    // request per 100 fills. GDAX has no cheaper way of getting a count.
+    pub fn count_fills(&self, product_id: Option<&str>) -> Result<usize, Error> {
+        let mut count = 0;
+        let mut pages = self.get_fills_stream(None, product_id);
+
+        while let Some(page) = pages.next_page() {
+            count += page?.len();
+        }
+        Ok(count)
+    }
+
+    // Fills created at or after `since`, newest first. GDAX pages from
+    // newest to oldest so we can stop at the first page that reaches
+    // back past `since` rather than walking the entire history.
+    pub fn get_fills_since(&self, product_id: Option<&str>, since: DateTime<UTC>)
+        -> Result<Vec<Fill>, Error> {
+
+        let mut fills = Vec::new();
+        let mut pages = self.get_fills_stream(None, product_id);
+
+        while let Some(page) = pages.next_page() {
+            let page = page?;
+            let reached_since = page.iter().any(|fill| fill.created_at < since);
+            fills.extend(page.into_iter().filter(|fill| fill.created_at >= since));
+
+            if reached_since {
+                break;
+            }
+        }
+        Ok(fills)
+    }
+
+    // Maker and taker volume since `since`, e.g. the last 30 days
+    // for working out where the fee tier will land
+    pub fn volume_breakdown(&self, since: DateTime<UTC>) -> Result<VolumeBreakdown, Error> {
+        let mut by_product = HashMap::new();
+        for fill in self.get_fills_since(None, since)? {
+            let volume = by_product.entry(fill.product_id).or_insert_with(LiquidityVolume::default);
+            match fill.liquidity {
+                Liquidity::Maker => volume.maker += fill.price * fill.size,
+                Liquidity::Taker => volume.taker += fill.price * fill.size
+            }
+        }
+
+        Ok(VolumeBreakdown {
+            since: since,
+            by_product: by_product
+        })
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Clones share the journal so only the last one flushes it
+        let last = self.journal.as_ref().map_or(false, |journal| Arc::strong_count(journal) == 1);
+        if last {
+            if let Err(err) = self.flush() {
+                warn!("unable to flush the order journal: {:?}", err);
+            }
+        }
+    }
 }
 
 impl Deref for Client {
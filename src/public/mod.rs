@@ -1,64 +1,408 @@
-use chrono::{DateTime, UTC};
+use chrono::{self, DateTime, NaiveDateTime, UTC};
 use hyper::client::Client as HttpClient;
-use hyper::header::UserAgent;
-use serde::Deserialize;
+use hyper::header::{Connection, Headers, UserAgent};
+use serde::{self, Deserialize};
 use serde_json::de;
+use serde_json::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Deref;
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use super::{Cursor, Page, PaginatedStream};
 use super::Error;
+use super::Execution;
+use super::FailureMode;
+use super::builder::Config;
+use super::numeric;
 use super::Side;
+use super::rate_limit::{RateLimiter, RateLimitStatus};
 
-const PUBLIC_API_URL: &'static str = "https://api.gdax.com";
 
+// How long a measured server time offset is reused before measuring again
+const TIME_OFFSET_TTL_SECS: u64 = 60;
+
+// Candle widths accepted by `/products/<id>/candles`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Granularity {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    SixHours,
+    OneDay
+}
+
+// Most candles GDAX returns for a single request
+const MAX_CANDLES: i64 = 300;
+
+impl Granularity {
+    pub fn seconds(&self) -> u64 {
+        match *self {
+            Granularity::OneMinute => 60,
+            Granularity::FiveMinutes => 300,
+            Granularity::FifteenMinutes => 900,
+            Granularity::OneHour => 3600,
+            Granularity::SixHours => 21600,
+            Granularity::OneDay => 86400
+        }
+    }
+
+    // Number of candles covering `start` to `end`, counting
+    // a partial interval at the end as a whole candle
+    pub fn candles_between(&self, start: DateTime<UTC>, end: DateTime<UTC>) -> u64 {
+        let span = (end - start).num_seconds();
+        if span <= 0 {
+            return 0;
+        }
+
+        let seconds = self.seconds() as i64;
+        ((span + seconds - 1) / seconds) as u64
+    }
+
+    // The longest range a single candles request can cover
+    pub fn max_range(&self) -> chrono::Duration {
+        chrono::Duration::seconds(MAX_CANDLES * self.seconds() as i64)
+    }
+}
+
+// Depth of `/products/<id>/book`:
+//   1: only the best bid and ask, aggregated
+//   2: the top 50 bids and asks, aggregated
+//   3: the full book, one entry per order
+// GDAX has no level that is both full depth and aggregated.
+#[derive(Clone, Copy, Debug)]
 pub enum Level {
     Best    = 1,
     Top50   = 2,
     Full    = 3
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Product {
     pub id: String,
     pub base_currency: String,
     pub quote_currency: String,
     pub base_min_size: f64,
     pub base_max_size: f64,
-    pub quote_increment: f64
+    pub base_increment: f64,
+    pub quote_increment: f64,
+    // Limits on market orders given in funds, not sent for every product
+    #[serde(default, deserialize_with = "numeric::opt_f64")]
+    pub min_market_funds: Option<f64>,
+    #[serde(default, deserialize_with = "numeric::opt_f64")]
+    pub max_market_funds: Option<f64>,
+    // Missing or unrecognised statuses come through as `Other` so one odd
+    // product doesn't fail the whole listing
+    #[serde(default)]
+    pub status: ProductStatus,
+    #[serde(default)]
+    pub trading_disabled: bool,
+    #[serde(default)]
+    pub cancel_only: bool,
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub limit_only: bool
+}
+
+impl Product {
+    pub fn can_trade(&self) -> bool {
+        self.status == ProductStatus::Online && !self.trading_disabled && !self.cancel_only
+    }
+
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_to(price, self.quote_increment)
+    }
+
+    // Sizes are rounded down so snapping never asks for
+    // more than the amount the caller started with
+    pub fn round_size(&self, size: f64) -> f64 {
+        floor_to(size, self.base_increment)
+    }
+
+    // Rounds towards the passive side of the book, buys down and sells up,
+    // so snapping to the increment never turns a maker order into a taker
+    pub fn round_price_for_side(&self, price: f64, side: Side) -> f64 {
+        match side {
+            Side::Buy => floor_to(price, self.quote_increment),
+            Side::Sell => ceil_to(price, self.quote_increment)
+        }
+    }
+
+    // Rounds a stop's trigger price towards the current price, sell stops
+    // up and buy stops down, so it triggers a little early rather than
+    // late. That's the same direction `round_price_for_side` rounds in.
+    pub fn round_stop_price(&self, price: f64, side: Side) -> f64 {
+        self.round_price_for_side(price, side)
+    }
+
+    // Prices and sizes written out to exactly as many decimal places
+    // as the increment has, e.g. "250.50" for a 0.01 increment
+    pub fn format_price(&self, price: f64) -> String {
+        format!("{:.*}", decimals(self.quote_increment), self.round_price(price))
+    }
+
+    pub fn format_size(&self, size: f64) -> String {
+        format!("{:.*}", decimals(self.base_increment), self.round_size(size))
+    }
+}
+
+// Number of decimal places needed to write `increment` exactly
+fn decimals(increment: f64) -> usize {
+    let mut scaled = increment;
+    for places in 0..12 {
+        if (scaled - scaled.round()).abs() < INCREMENT_EPSILON * scaled.max(1.) {
+            return places;
+        }
+        scaled *= 10.;
+    }
+    12
+}
+
+// Tolerance so values already on an increment aren't pushed
+// a whole step by floating point error when flooring or ceiling
+const INCREMENT_EPSILON: f64 = 1e-9;
+
+fn round_to(value: f64, increment: f64) -> f64 {
+    if increment > 0. {
+        (value / increment).round() * increment
+    } else {
+        value
+    }
+}
+
+fn floor_to(value: f64, increment: f64) -> f64 {
+    if increment > 0. {
+        (value / increment + INCREMENT_EPSILON).floor() * increment
+    } else {
+        value
+    }
+}
+
+fn ceil_to(value: f64, increment: f64) -> f64 {
+    if increment > 0. {
+        (value / increment - INCREMENT_EPSILON).ceil() * increment
+    } else {
+        value
+    }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProductStatus {
+    Online,
+    Offline,
+    Internal,
+    Delisted,
+    Other(String)
+}
+
+impl ProductStatus {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ProductStatus::Online => "online",
+            ProductStatus::Offline => "offline",
+            ProductStatus::Internal => "internal",
+            ProductStatus::Delisted => "delisted",
+            ProductStatus::Other(ref status) => status
+        }
+    }
+}
+
+impl Default for ProductStatus {
+    fn default() -> ProductStatus {
+        ProductStatus::Other(String::new())
+    }
+}
+
+// We manually implement Serialize for ProductStatus here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl serde::Serialize for ProductStatus {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// We manually implement Deserialize for ProductStatus here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl serde::Deserialize for ProductStatus {
+    fn deserialize<D>(deserializer: &mut D) -> Result<ProductStatus, D::Error>
+        where D: serde::Deserializer {
+
+        struct ProductStatusVisitor;
+        impl serde::de::Visitor for ProductStatusVisitor {
+            type Value = ProductStatus;
+
+            fn visit_str<E>(&mut self, v: &str) -> Result<Self::Value, E>
+                where E: serde::Error {
+                match &*v.to_lowercase() {
+                    "online" => Ok(ProductStatus::Online),
+                    "offline" => Ok(ProductStatus::Offline),
+                    "internal" => Ok(ProductStatus::Internal),
+                    "delisted" => Ok(ProductStatus::Delisted),
+                    _ => Ok(ProductStatus::Other(v.to_string()))
+                }
+            }
+        }
+        deserializer.deserialize(ProductStatusVisitor)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct BookEntry {
     pub price: f64,
     pub size: f64,
     pub num_orders: u64
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct FullBookEntry {
     pub price: f64,
     pub size: f64,
     pub order_id: Uuid
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct OrderBook<T> {
     pub sequence: usize,
     pub bids: Vec<T>,
     pub asks: Vec<T>
 }
 
-#[derive(Deserialize, Debug)]
+impl<T> OrderBook<T> {
+    // Keeps only the first `levels` entries on each side, which for
+    // a level 3 book means orders rather than price levels
+    pub fn truncate(&mut self, levels: usize) {
+        self.bids.truncate(levels);
+        self.bids.shrink_to_fit();
+        self.asks.truncate(levels);
+        self.asks.shrink_to_fit();
+    }
+}
+
+impl OrderBook<BookEntry> {
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) / 2.),
+            _ => None
+        }
+    }
+
+    // Average of the size-weighted bid and ask prices over
+    // the top `depth` levels on each side of the book
+    pub fn weighted_mid(&self, depth: usize) -> Option<f64> {
+        match (weighted_price(&self.bids, depth), weighted_price(&self.asks, depth)) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.),
+            _ => None
+        }
+    }
+
+    // Mid price skewed towards the side with less resting size
+    // at the top of the book, i.e. the side more likely to be taken out
+    pub fn microprice(&self) -> Option<f64> {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) if bid.size + ask.size > 0. => {
+                Some((bid.price * ask.size + ask.price * bid.size) / (bid.size + ask.size))
+            }
+            _ => None
+        }
+    }
+
+    // (bid size - ask size) / (bid size + ask size) over the top `depth`
+    // levels, from -1 (all asks) to 1 (all bids). An empty book is 0.
+    pub fn imbalance(&self, depth: usize) -> f64 {
+        let bid_size = total_size(&self.bids, depth);
+        let ask_size = total_size(&self.asks, depth);
+
+        if bid_size + ask_size > 0. {
+            (bid_size - ask_size) / (bid_size + ask_size)
+        } else {
+            0.
+        }
+    }
+}
+
+fn total_size(entries: &[BookEntry], depth: usize) -> f64 {
+    entries.iter().take(depth).fold(0., |size, entry| size + entry.size)
+}
+
+fn weighted_price(entries: &[BookEntry], depth: usize) -> Option<f64> {
+    let (notional, size) = entries.iter()
+                                  .take(depth)
+                                  .fold((0., 0.), |(notional, size), entry| {
+                                      (notional + entry.price * entry.size, size + entry.size)
+                                  });
+    if size > 0. {
+        Some(notional / size)
+    } else {
+        None
+    }
+}
+
+impl OrderBook<FullBookEntry> {
+    pub fn aggregate(&self) -> OrderBook<BookEntry> {
+        OrderBook {
+            sequence: self.sequence,
+            bids: aggregate_levels(&self.bids),
+            asks: aggregate_levels(&self.asks)
+        }
+    }
+
+    // Maps each order id to the side of the book it rests on and its
+    // position in that side's entries. The index goes stale as soon as
+    // entries are added or removed, so rebuild it after modifying the book.
+    pub fn index_by_order_id(&self) -> HashMap<Uuid, (Side, usize)> {
+        let bids = self.bids.iter().enumerate().map(|(i, entry)| (entry.order_id, (Side::Buy, i)));
+        let asks = self.asks.iter().enumerate().map(|(i, entry)| (entry.order_id, (Side::Sell, i)));
+        bids.chain(asks).collect()
+    }
+}
+
+// Entries are sorted by price so orders resting at the
+// same price are always next to each other
+fn aggregate_levels(entries: &[FullBookEntry]) -> Vec<BookEntry> {
+    let mut levels: Vec<BookEntry> = Vec::new();
+    for entry in entries {
+        if let Some(level) = levels.last_mut() {
+            if level.price == entry.price {
+                level.size += entry.size;
+                level.num_orders += 1;
+                continue;
+            }
+        }
+
+        levels.push(BookEntry {
+            price: entry.price,
+            size: entry.size,
+            num_orders: 1
+        });
+    }
+    levels
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Tick {
     pub trade_id: u64,
     pub price: f64,
     pub size: f64,
     pub bid: f64,
     pub ask: f64,
+    #[serde(default, deserialize_with = "numeric::f64_or_zero")]
     pub volume: f64,
     pub time: DateTime<UTC>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Trade {
     pub time: DateTime<UTC>,
     pub trade_id: u64,
@@ -67,97 +411,496 @@ pub struct Trade {
     pub side: Side,
 }
 
-#[derive(Deserialize, Debug)]
+// Trades are identified by their `trade_id`, so two copies of the same
+// trade from different sources compare equal and dedupe in a set, while
+// ordering is chronological with `trade_id` breaking ties
+impl PartialEq for Trade {
+    fn eq(&self, other: &Trade) -> bool {
+        self.trade_id == other.trade_id
+    }
+}
+
+impl Eq for Trade {}
+
+impl PartialOrd for Trade {
+    fn partial_cmp(&self, other: &Trade) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Trade {
+    fn cmp(&self, other: &Trade) -> Ordering {
+        (self.time, self.trade_id).cmp(&(other.time, other.trade_id))
+    }
+}
+
+impl Execution for Trade {
+    fn price(&self) -> f64 {
+        self.price
+    }
+
+    fn size(&self) -> f64 {
+        self.size
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn time(&self) -> DateTime<UTC> {
+        self.time
+    }
+}
+
+impl Trade {
+    // GDAX reports the maker's side of a trade, so a trade
+    // marked `sell` was a taker lifting the offer, i.e. a buy
+    pub fn taker_side(&self) -> Side {
+        match self.side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy
+        }
+    }
+}
+
+// Cumulative volume delta: taker buy volume minus taker sell volume
+// over the trades in the last `window`, measured back from the most
+// recent trade seen rather than the local clock
+pub struct CvdAccumulator {
+    window: chrono::Duration,
+    deltas: VecDeque<(DateTime<UTC>, f64)>,
+    value: f64
+}
+
+impl CvdAccumulator {
+    pub fn new(window: chrono::Duration) -> CvdAccumulator {
+        CvdAccumulator {
+            window: window,
+            deltas: VecDeque::new(),
+            value: 0.
+        }
+    }
+
+    pub fn add(&mut self, trade: &Trade) {
+        let delta = match trade.taker_side() {
+            Side::Buy => trade.size,
+            Side::Sell => -trade.size
+        };
+
+        // Trades can arrive slightly out of order, keep them sorted by time
+        let at = self.deltas.iter().rposition(|&(time, _)| time <= trade.time).map_or(0, |i| i + 1);
+        self.deltas.insert(at, (trade.time, delta));
+        self.value += delta;
+
+        let cutoff = self.deltas.back().unwrap().0 - self.window;
+        while self.deltas.front().map_or(false, |&(time, _)| time < cutoff) {
+            let (_, delta) = self.deltas.pop_front().unwrap();
+            self.value -= delta;
+        }
+    }
+
+    pub fn extend<'a, I>(&mut self, trades: I)
+        where I: IntoIterator<Item = &'a Trade>
+    {
+        for trade in trades {
+            self.add(trade);
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.deltas.clear();
+        self.value = 0.;
+    }
+}
+
+// `time` is kept as the epoch seconds GDAX sends rather than a
+// `DateTime` so candles serialize back the way they were received
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Candle {
     pub time: u64,
     pub low: f64,
     pub high: f64,
     pub open: f64,
     pub close: f64,
+    #[serde(deserialize_with = "numeric::f64_or_zero")]
     pub volume: f64
 }
 
-#[derive(Deserialize, Debug)]
+pub fn total_volume(candles: &[Candle]) -> f64 {
+    candles.iter().fold(0., |volume, candle| volume + candle.volume)
+}
+
+// An approximation of the VWAP from candles alone, weighting each
+// candle's typical price, (high + low + close) / 3, by its volume.
+// An exact VWAP needs the individual trades.
+pub fn vwap(candles: &[Candle]) -> Option<f64> {
+    let volume = total_volume(candles);
+    if volume > 0. {
+        let notional = candles.iter().fold(0., |notional, candle| {
+            notional + (candle.high + candle.low + candle.close) / 3. * candle.volume
+        });
+        Some(notional / volume)
+    } else {
+        None
+    }
+}
+
+// Collapses a series of candles, in any order, into a
+// single candle spanning the whole series
+pub fn ohlc_merge(candles: &[Candle]) -> Option<Candle> {
+    let (first, last) = match (candles.iter().min_by_key(|candle| candle.time),
+                               candles.iter().max_by_key(|candle| candle.time)) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return None
+    };
+
+    Some(Candle {
+        time: first.time,
+        low: candles.iter().fold(first.low, |low, candle| low.min(candle.low)),
+        high: candles.iter().fold(first.high, |high, candle| high.max(candle.high)),
+        open: first.open,
+        close: last.close,
+        volume: total_volume(candles)
+    })
+}
+
+// Aggregates candles of granularity `from` into candles of granularity
+// `to`, which has to be a whole multiple of `from`. Buckets are aligned
+// to the epoch like GDAX's own candles and each candle is stamped with
+// the start of its bucket. Returned oldest first.
+pub fn resample(candles: &[Candle], from: Granularity, to: Granularity) -> Result<Vec<Candle>, Error> {
+    let (from_secs, to_secs) = (from.seconds(), to.seconds());
+    if to_secs < from_secs || to_secs % from_secs != 0 {
+        return Err(Error::InvalidArgument(format!("can't resample {:?} candles into {:?}", from, to)));
+    }
+
+    let mut sorted = candles.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|candle| candle.time);
+
+    let mut resampled: Vec<Candle> = Vec::new();
+    for candle in sorted {
+        let bucket = candle.time - candle.time % to_secs;
+        if let Some(last) = resampled.last_mut() {
+            if last.time == bucket {
+                last.low = last.low.min(candle.low);
+                last.high = last.high.max(candle.high);
+                last.close = candle.close;
+                last.volume += candle.volume;
+                continue;
+            }
+        }
+
+        resampled.push(Candle {
+            time: bucket,
+            low: candle.low,
+            high: candle.high,
+            open: candle.open,
+            close: candle.close,
+            volume: candle.volume
+        });
+    }
+    Ok(resampled)
+}
+
+// A product that has only just listed may not have an open, high
+// or low yet, and its volume can come back blank
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Stats {
-    pub open: f64,
-    pub high: f64,
-    pub low: f64,
+    #[serde(default, deserialize_with = "numeric::opt_f64")]
+    pub open: Option<f64>,
+    #[serde(default, deserialize_with = "numeric::opt_f64")]
+    pub high: Option<f64>,
+    #[serde(default, deserialize_with = "numeric::opt_f64")]
+    pub low: Option<f64>,
+    #[serde(default, deserialize_with = "numeric::f64_or_zero")]
     pub volume: f64
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Currency {
     pub id: String,
     pub name: String,
     pub min_size: f64
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Time {
     pub iso: DateTime<UTC>,
     pub epoch: f64
 }
 
+impl Time {
+    // The server time from `epoch`, keeping its fractional part down
+    // to the nanosecond instead of truncating to whole seconds. Should
+    // agree with `iso`, which makes for a handy cross-check.
+    pub fn to_datetime(&self) -> DateTime<UTC> {
+        let secs = self.epoch.floor();
+        let nanos = ((self.epoch - secs) * 1e9).round().min(999_999_999.) as u32;
+        DateTime::from_utc(NaiveDateTime::from_timestamp(secs as i64, nanos), UTC)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProductSnapshot {
+    pub ticker: Tick,
+    pub stats: Stats,
+    pub best: Option<OrderBook<BookEntry>>
+}
+
+// Levels 1 and 2 come back aggregated by price, level 3 per order
+#[derive(Debug)]
+pub enum OrderBookResponse {
+    Aggregated(OrderBook<BookEntry>),
+    Full(OrderBook<FullBookEntry>)
+}
+
+// Clones share the same connection pool and rate limiter
+#[derive(Clone)]
 pub struct Client {
-    http_client: HttpClient,
+    http_client: Arc<HttpClient>,
+    rate_limiter: Arc<RateLimiter>,
+    time_offset: Arc<Mutex<Option<(Instant, chrono::Duration)>>>,
+    default_headers: Headers,
+    config: Arc<Config>
 }
 
 impl Client {
     pub fn new() -> Client {
+        Client::from_config(Config::new(), Arc::new(HttpClient::new()))
+    }
+
+    // Used by `ClientBuilder`, `Config` can't be named outside the crate
+    pub fn from_config(config: Config, http_client: Arc<HttpClient>) -> Client {
         Client {
-            http_client: HttpClient::new()
+            http_client: http_client,
+            rate_limiter: Arc::new(RateLimiter::new()),
+            time_offset: Arc::new(Mutex::new(None)),
+            default_headers: Headers::new(),
+            config: Arc::new(config)
         }
     }
 
-    fn get_and_decode<T>(&self, url: &str) -> Result<T, Error>
+    // Extra headers sent along with every request, e.g. for a
+    // gateway in front of GDAX that wants a token of its own
+    pub fn with_default_headers(mut self, headers: Headers) -> Client {
+        self.default_headers = headers;
+        self
+    }
+
+    // Sends `Connection: close` so every connection is torn down after
+    // its response rather than kept alive in the pool. Meant for short
+    // lived processes making a request or two before exiting, where an
+    // idle pooled connection only adds teardown work and the odd error
+    // from a half-closed socket.
+    pub fn with_connection_close(mut self) -> Client {
+        Arc::make_mut(&mut self.config).connection_close = true;
+        self
+    }
+
+    // Runs `f` against a clone of this client on another thread
+    fn spawn<T, F>(&self, product: &str, f: F) -> thread::JoinHandle<Result<T, Error>>
+        where T: Send + 'static,
+              F: FnOnce(&Client, &str) -> Result<T, Error> + Send + 'static
+    {
+        let client = self.clone();
+        let product = product.to_owned();
+        thread::spawn(move || f(&client, &product))
+    }
+
+    pub fn rate_limit_status(&self) -> Option<RateLimitStatus> {
+        self.rate_limiter.status()
+    }
+
+    // Retries, if configured to, when the request fails in transit
+    fn get_and_decode_with_headers<T>(&self, path: &str) -> Result<(T, Headers), Error>
         where T: Deserialize
     {
+        let mut attempts = 0;
+        loop {
+            match self.try_get_and_decode(path) {
+                Err(Error::Connection(err)) => {
+                    if attempts == self.config.retries {
+                        return Err(Error::Connection(err));
+                    }
+                    attempts += 1;
+                    warn!("retrying {} after {} ({} of {})", path, err, attempts, self.config.retries);
+                }
+                result => return result
+            }
+        }
+    }
 
-        let mut res = self.http_client.get(url)
-                                      .header(UserAgent("rust-gdax-client/0.1.0".to_owned()))
+    fn try_get_and_decode<T>(&self, path: &str) -> Result<(T, Headers), Error>
+        where T: Deserialize
+    {
+        if self.config.rate_limit {
+            self.rate_limiter.wait();
+        }
+
+        let mut headers = Headers::new();
+        headers.set(UserAgent(self.config.user_agent.clone()));
+        if self.config.connection_close {
+            headers.set(Connection::close());
+        }
+        headers.extend(self.default_headers.iter());
+
+        let mut res = self.http_client.get(&format!("{}{}", self.config.base_url, path))
+                                      .headers(headers)
                                       .send()?;
 
+        self.rate_limiter.update(&res.headers);
+
         if !res.status.is_success() {
-            return Err(Error::Api(de::from_reader(&mut res)?));
+            return Err(super::api_error(&mut res));
         }
 
-        Ok(de::from_reader(&mut res)?)
+        let decoded = de::from_reader(&mut res)?;
+        Ok((decoded, res.headers.clone()))
+    }
+
+    fn get_and_decode<T>(&self, path: &str) -> Result<T, Error>
+        where T: Deserialize
+    {
+        self.get_and_decode_with_headers(path).map(|(decoded, _)| decoded)
+    }
+
+    pub fn get_raw(&self, path: &str) -> Result<Value, Error> {
+        self.get_and_decode(path)
     }
 
     pub fn get_products(&self) -> Result<Vec<Product>, Error> {
-        self.get_and_decode(&format!("{}/products", PUBLIC_API_URL))
+        self.get_and_decode("/products")
     }
 
+    // Level 1: the best bid and ask only
     pub fn get_best_order(&self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Best as u8))
     }
 
+    // Level 2: the top 50 price levels on each side
     pub fn get_top50_orders(&self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Top50 as u8))
     }
 
+    // Level 3: every resting order, not aggregated
     pub fn get_full_book(&self, product: &str) -> Result<OrderBook<FullBookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Full as u8))
     }
 
+    // Every price level on each side. GDAX doesn't serve this directly
+    // so it's built by fetching the full book and aggregating it here.
+    pub fn get_aggregated_book(&self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
+        Ok(self.get_full_book(product)?.aggregate())
+    }
+
+    // The top `levels` aggregated price levels on each side, built from
+    // the full book, which is dropped as soon as it's been aggregated
+    pub fn get_full_book_top(&self, product: &str, levels: usize) -> Result<OrderBook<BookEntry>, Error> {
+        let mut book = self.get_aggregated_book(product)?;
+        book.truncate(levels);
+        Ok(book)
+    }
+
     pub fn get_product_ticker(&self, product: &str) -> Result<Tick, Error> {
-        self.get_and_decode(&format!("{}/products/{}/ticker", PUBLIC_API_URL, product))
+        self.get_and_decode(&format!("/products/{}/ticker", product))
     }
 
     pub fn get_trades(&self, product: &str) -> Result<Vec<Trade>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/trades", PUBLIC_API_URL, product))
+        self.get_trades_page(product, None).map(|page| page.items)
     }
 
-    // XXX: Returns invalid interval?
+    pub fn get_trades_page(&self, product: &str, cursor: Option<&Cursor>) -> Result<Page<Trade>, Error> {
+        let path = format!("/products/{}/trades", product);
+        let (trades, headers) = self.get_and_decode_with_headers(&super::with_query(&path, vec![], cursor))?;
+        Ok(Page::from_headers(trades, &headers))
+    }
+
+    pub fn get_trades_stream<'a>(&'a self, product: &'a str) -> PaginatedStream<'a, Trade> {
+        PaginatedStream::new(move |cursor| self.get_trades_page(product, cursor))
+    }
+
+    // Trades newer than `last_trade_id`, oldest first. Pages back from the
+    // most recent trade and stops at the first page reaching `last_trade_id`.
+    pub fn get_trades_since(&self, product: &str, last_trade_id: u64) -> Result<Vec<Trade>, Error> {
+        let mut trades = Vec::new();
+        let mut pages = self.get_trades_stream(product);
+
+        while let Some(page) = pages.next_page() {
+            let page = page?;
+            let reached_last = page.iter().any(|trade| trade.trade_id <= last_trade_id);
+            trades.extend(page.into_iter().filter(|trade| trade.trade_id > last_trade_id));
+
+            if reached_last {
+                break;
+            }
+        }
+
+        trades.sort();
+        Ok(trades)
+    }
+
+    // Polls every product's trades from one background thread, sending
+    // each new trade once, oldest first per product. Trades from before
+    // the call aren't sent. Dropping the returned `TradeFirehose` stops the
+    // thread before its next poll.
+    pub fn trade_firehose(&self, products: &[&str], poll_interval: Duration) -> TradeFirehose {
+        let (sender, receiver) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let client = self.clone();
+        let thread_stopped = stopped.clone();
+        let mut last_seen = products.iter()
+                                    .map(|product| (product.to_string(), None))
+                                    .collect::<Vec<(String, Option<u64>)>>();
+
+        thread::spawn(move || {
+            loop {
+                for &mut (ref product, ref mut last_trade_id) in &mut last_seen {
+                    if thread_stopped.load(AtomicOrdering::SeqCst) {
+                        return;
+                    }
+
+                    let trades = match *last_trade_id {
+                        Some(last) => client.get_trades_since(product, last),
+                        None => client.get_trades(product).map(|trades| {
+                            *last_trade_id = trades.iter().map(|trade| trade.trade_id).max();
+                            Vec::new()
+                        })
+                    };
+
+                    match trades {
+                        Ok(trades) => {
+                            for trade in trades {
+                                *last_trade_id = Some(trade.trade_id);
+                                if sender.send((product.clone(), trade)).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        Err(err) => warn!("unable to poll trades for {}: {:?}", product, err)
+                    }
+                }
+                thread::sleep(poll_interval);
+            }
+        });
+
+        TradeFirehose {
+            receiver: receiver,
+            stopped: stopped
+        }
+    }
+
+    // XXX: GDAX has been seen returning candles at a different interval
+    // than the one requested, so the spacing is checked before returning
     pub fn get_historic_rates(&self,
                               product: &str,
                               start_time: DateTime<UTC>,
@@ -165,23 +908,346 @@ impl Client {
                               granularity: u64)
         -> Result<Vec<Candle>, Error> {
 
-        self.get_and_decode(&format!("{}/products/{}/candles?start={}&end={}&granularity={}",
-                                     PUBLIC_API_URL,
-                                     product,
-                                     start_time.to_rfc3339(),
-                                     end_time.to_rfc3339(),
-                                     granularity))
+        let candles: Vec<Candle> = self.get_and_decode(&format!("/products/{}/candles?start={}&end={}&granularity={}",
+                                                               product,
+                                                               start_time.to_rfc3339(),
+                                                               end_time.to_rfc3339(),
+                                                               granularity))?;
+        check_candle_spacing(&candles, granularity)?;
+        Ok(candles)
+    }
+
+    // The last `count` candles up to now, oldest first. Split across
+    // several requests when that's more than GDAX returns for one.
+    pub fn get_recent_candles(&self, product: &str, granularity: Granularity, count: u32)
+        -> Result<Vec<Candle>, Error> {
+
+        let end = UTC::now();
+        let start = end - chrono::Duration::seconds(count as i64 * granularity.seconds() as i64);
+
+        let mut candles = Vec::new();
+        let mut chunk_start = start;
+        while chunk_start < end {
+            let chunk_end = if chunk_start + granularity.max_range() < end {
+                chunk_start + granularity.max_range()
+            } else {
+                end
+            };
+            candles.extend(self.get_historic_rates(product, chunk_start, chunk_end, granularity.seconds())?);
+            chunk_start = chunk_end;
+        }
+
+        // Chunks share their boundary candle
+        candles.sort_by_key(|candle| candle.time);
+        let mut last_time = None;
+        candles.retain(|candle| {
+            let duplicate = last_time == Some(candle.time);
+            last_time = Some(candle.time);
+            !duplicate
+        });
+
+        let excess = candles.len().saturating_sub(count as usize);
+        candles.drain(..excess);
+        Ok(candles)
     }
 
     pub fn get_24hr_stats(&self, product: &str) -> Result<Stats, Error> {
-        self.get_and_decode(&format!("{}/products/{}/stats", PUBLIC_API_URL, product))
+        self.get_and_decode(&format!("/products/{}/stats", product))
+    }
+
+    pub fn get_all_stats(&self, products: &[&str], mode: FailureMode) -> Vec<Result<Stats, Error>> {
+        mode.run(products, |product| self.get_24hr_stats(product))
+    }
+
+    // Every product paired with its 24hr stats. The stats are fetched
+    // a few at a time, each request still going through the rate limiter,
+    // and a product whose stats can't be fetched is left out.
+    pub fn get_products_with_stats(&self) -> Result<Vec<(Product, Stats)>, Error> {
+        let products = self.get_products()?;
+        let ids = products.iter().map(|product| product.id.clone()).collect();
+        let stats = super::fan_out(self, ids, |client, id| client.get_24hr_stats(&id));
+
+        Ok(products.into_iter().zip(stats).filter_map(|(product, stats)| {
+            match stats {
+                Ok(stats) => Some((product, stats)),
+                Err(err) => {
+                    warn!("skipping {}, unable to fetch its stats: {:?}", product.id, err);
+                    None
+                }
+            }
+        }).collect())
     }
 
     pub fn get_currencies(&self) -> Result<Vec<Currency>, Error> {
-        self.get_and_decode(&format!("{}/currencies", PUBLIC_API_URL))
+        self.get_and_decode("/currencies")
     }
 
     pub fn get_time(&self) -> Result<Time, Error> {
-        self.get_and_decode(&format!("{}/time", PUBLIC_API_URL))
+        self.get_and_decode("/time")
+    }
+
+    // Fetches the ticker, 24hr stats and, if `with_book` is set,
+    // the best bid and ask concurrently
+    pub fn get_product_snapshot(&self, product: &str, with_book: bool)
+        -> Result<ProductSnapshot, Error> {
+
+        let stats = self.spawn(product, |client, product| client.get_24hr_stats(product));
+        let best = if with_book {
+            Some(self.spawn(product, |client, product| client.get_best_order(product)))
+        } else {
+            None
+        };
+
+        let ticker = self.get_product_ticker(product);
+        let stats = join(stats);
+        let best = match best {
+            Some(best) => Some(join(best)?),
+            None => None
+        };
+
+        Ok(ProductSnapshot {
+            ticker: ticker?,
+            stats: stats?,
+            best: best
+        })
     }
+
+    // Fetches the book at the given level and the ticker concurrently
+    pub fn get_book_and_ticker(&self, product: &str, level: Level)
+        -> Result<(OrderBookResponse, Tick), Error> {
+
+        let book = self.spawn(product, move |client, product| {
+            match level {
+                Level::Best => client.get_best_order(product).map(OrderBookResponse::Aggregated),
+                Level::Top50 => client.get_top50_orders(product).map(OrderBookResponse::Aggregated),
+                Level::Full => client.get_full_book(product).map(OrderBookResponse::Full)
+            }
+        });
+
+        let ticker = self.get_product_ticker(product);
+        let book = join(book);
+
+        Ok((book?, ticker?))
+    }
+
+    // Returns how far the server clock is ahead of the local clock.
+    // The local time is taken halfway through the request to
+    // account for the round trip.
+    pub fn check_time_skew(&self) -> Result<chrono::Duration, Error> {
+        let before = UTC::now();
+        let server_time = self.get_time()?;
+        let after = UTC::now();
+
+        let local_time = before + (after - before) / 2;
+        Ok(server_time.to_datetime() - local_time)
+    }
+
+    // Like `check_time_skew` but reuses the last measurement
+    // for up to a minute instead of hitting `/time` every call
+    pub fn server_time_offset(&self) -> Result<chrono::Duration, Error> {
+        if let Some((measured_at, offset)) = *self.time_offset.lock().unwrap() {
+            if measured_at.elapsed() < Duration::from_secs(TIME_OFFSET_TTL_SECS) {
+                return Ok(offset);
+            }
+        }
+
+        let offset = self.check_time_skew()?;
+        *self.time_offset.lock().unwrap() = Some((Instant::now(), offset));
+        Ok(offset)
+    }
+}
+
+fn join<T>(handle: thread::JoinHandle<T>) -> T {
+    match handle.join() {
+        Ok(result) => result,
+        Err(payload) => panic::resume_unwind(payload)
+    }
+}
+
+// One step of a conversion: trade along `product_id` into `to`.
+// `inverse` is set when `to` is the product's base currency, so the
+// amount gets divided by the price rather than multiplied.
+#[derive(Clone, Debug)]
+struct Conversion {
+    product_id: String,
+    to: String,
+    inverse: bool
+}
+
+// Currencies linked by the markets trading them, for valuing an amount
+// in a currency it has no market against, e.g. BTC in EUR through USD.
+// Rates are indicative, taken from the last trade on each market.
+pub struct ConversionGraph {
+    client: Client,
+    edges: HashMap<String, Vec<Conversion>>
+}
+
+impl ConversionGraph {
+    pub fn new(client: &Client) -> Result<ConversionGraph, Error> {
+        let products = client.get_products()?;
+        Ok(ConversionGraph::from_products(client, &products))
+    }
+
+    // Products that can't currently trade are left out
+    pub fn from_products(client: &Client, products: &[Product]) -> ConversionGraph {
+        let mut edges = HashMap::new();
+        for product in products.iter().filter(|product| product.can_trade()) {
+            edges.entry(product.base_currency.clone()).or_insert_with(Vec::new).push(Conversion {
+                product_id: product.id.clone(),
+                to: product.quote_currency.clone(),
+                inverse: false
+            });
+            edges.entry(product.quote_currency.clone()).or_insert_with(Vec::new).push(Conversion {
+                product_id: product.id.clone(),
+                to: product.base_currency.clone(),
+                inverse: true
+            });
+        }
+
+        ConversionGraph {
+            client: client.clone(),
+            edges: edges
+        }
+    }
+
+    // Product ids to go through to get from one currency to the other,
+    // taking as few steps as possible
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        self.conversions(from, to)
+            .map(|conversions| conversions.into_iter().map(|conversion| conversion.product_id).collect())
+    }
+
+    // Breadth first search so the path uses the fewest markets
+    fn conversions(&self, from: &str, to: &str) -> Option<Vec<Conversion>> {
+        let mut came_from: HashMap<String, Option<(String, Conversion)>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        came_from.insert(from.to_owned(), None);
+        queue.push_back(from.to_owned());
+
+        while let Some(currency) = queue.pop_front() {
+            if currency == to {
+                break;
+            }
+
+            for conversion in self.edges.get(&currency).into_iter().flat_map(|edges| edges) {
+                if !came_from.contains_key(&conversion.to) {
+                    came_from.insert(conversion.to.clone(), Some((currency.clone(), conversion.clone())));
+                    queue.push_back(conversion.to.clone());
+                }
+            }
+        }
+
+        if !came_from.contains_key(to) {
+            return None;
+        }
+
+        let mut path = Vec::new();
+        let mut currency = to.to_owned();
+        while let Some(&Some((ref previous, ref conversion))) = came_from.get(&currency) {
+            path.push(conversion.clone());
+            currency = previous.clone();
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // Units of `to` per unit of `from`
+    pub fn rate(&self, from: &str, to: &str) -> Result<f64, Error> {
+        let conversions = match self.conversions(from, to) {
+            Some(conversions) => conversions,
+            None => return Err(Error::InvalidArgument(format!("no markets convert {} to {}", from, to)))
+        };
+
+        let mut rate = 1.;
+        for conversion in conversions {
+            let price = self.client.get_product_ticker(&conversion.product_id)?.price;
+            if price <= 0. {
+                return Err(Error::InvalidResponse(format!("{} has no price", conversion.product_id)));
+            }
+
+            if conversion.inverse {
+                rate /= price;
+            } else {
+                rate *= price;
+            }
+        }
+        Ok(rate)
+    }
+
+    pub fn convert_amount(&self, from: &str, to: &str, amount: f64) -> Result<f64, Error> {
+        self.rate(from, to).map(|rate| amount * rate)
+    }
+}
+
+// Intervals without any trades are left out so consecutive candles
+// may be several intervals apart, but never a fraction of one
+fn check_candle_spacing(candles: &[Candle], granularity: u64) -> Result<(), Error> {
+    if granularity == 0 {
+        return Ok(());
+    }
+
+    let misaligned = candles.windows(2)
+                            .filter(|pair| {
+                                let gap = if pair[0].time > pair[1].time {
+                                    pair[0].time - pair[1].time
+                                } else {
+                                    pair[1].time - pair[0].time
+                                };
+                                gap == 0 || gap % granularity != 0
+                            })
+                            .map(|pair| pair[1].time)
+                            .collect::<Vec<_>>();
+
+    if misaligned.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidResponse(format!("candles not spaced by {}s at {:?}",
+                                           granularity,
+                                           misaligned)))
+    }
+}
+
+// Receives the trades sent by `Client::trade_firehose`. The polling
+// thread only notices a dropped channel when it has a trade to send, so
+// this also tells it to stop on drop.
+pub struct TradeFirehose {
+    receiver: Receiver<(String, Trade)>,
+    stopped: Arc<AtomicBool>
+}
+
+impl Deref for TradeFirehose {
+    type Target = Receiver<(String, Trade)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+impl Drop for TradeFirehose {
+    fn drop(&mut self) {
+        self.stopped.store(true, AtomicOrdering::SeqCst);
+    }
+}
+
+// Spawns a thread that checks the clock skew against the server every
+// `interval` and logs a warning whenever it exceeds `threshold`. Uses a
+// clone of `client`, so it goes to the same server with the same headers.
+// Signed requests start failing once the skew reaches 30 seconds.
+pub fn spawn_time_skew_monitor(client: &Client, interval: Duration, threshold: chrono::Duration)
+    -> thread::JoinHandle<()> {
+
+    let client = client.clone();
+    thread::spawn(move || {
+        loop {
+            match client.check_time_skew() {
+                Ok(skew) if skew > threshold || skew < -threshold => {
+                    warn!("local clock is off from the GDAX server by {}ms",
+                          skew.num_milliseconds());
+                }
+                Ok(_) => {}
+                Err(err) => warn!("unable to check clock skew: {:?}", err)
+            }
+            thread::sleep(interval);
+        }
+    })
 }
@@ -0,0 +1,76 @@
+use chrono::{self, DateTime, NaiveDateTime, UTC};
+use hyper::header::Headers;
+use std::str;
+use std::sync::Mutex;
+use std::thread;
+
+const REMAINING_HEADER: &'static str = "rate-limit-remaining";
+const RESET_HEADER: &'static str = "rate-limit-reset";
+
+// Once fewer than this many requests remain in the
+// current window we start spacing requests out
+const LOW_WATERMARK: u64 = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitStatus {
+    pub remaining: u64,
+    pub reset: Option<DateTime<UTC>>
+}
+
+// Throttles requests based on the budget GDAX reports back
+// in its response headers rather than a local estimate
+pub struct RateLimiter {
+    status: Mutex<Option<RateLimitStatus>>
+}
+
+impl RateLimiter {
+    pub fn new() -> RateLimiter {
+        RateLimiter {
+            status: Mutex::new(None)
+        }
+    }
+
+    pub fn status(&self) -> Option<RateLimitStatus> {
+        *self.status.lock().unwrap()
+    }
+
+    pub fn update(&self, headers: &Headers) {
+        if let Some(remaining) = header_value(headers, REMAINING_HEADER) {
+            let reset = header_value(headers, RESET_HEADER).map(|reset| {
+                DateTime::from_utc(NaiveDateTime::from_timestamp(reset as i64, 0), UTC)
+            });
+
+            *self.status.lock().unwrap() = Some(RateLimitStatus {
+                remaining: remaining,
+                reset: reset
+            });
+        }
+    }
+
+    // Blocks while the remaining budget is low, spreading whatever
+    // is left evenly over the time until the window resets
+    pub fn wait(&self) {
+        let delay = match self.status() {
+            Some(RateLimitStatus { remaining, reset: Some(reset) }) if remaining < LOW_WATERMARK => {
+                let until_reset = reset - UTC::now();
+                if until_reset > chrono::Duration::zero() {
+                    (until_reset / (remaining as i32 + 1)).to_std().ok()
+                } else {
+                    None
+                }
+            }
+            _ => None
+        };
+
+        if let Some(delay) = delay {
+            thread::sleep(delay);
+        }
+    }
+}
+
+fn header_value(headers: &Headers, name: &str) -> Option<u64> {
+    headers.get_raw(name)
+           .and_then(|values| values.first())
+           .and_then(|value| str::from_utf8(value).ok())
+           .and_then(|value| value.trim().parse().ok())
+}
@@ -0,0 +1,116 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use std::sync::Mutex;
+use time::get_time;
+
+use super::Error;
+
+pub fn check_secret(secret: &str) -> Result<(), Error> {
+    STANDARD.decode(secret)?;
+    Ok(())
+}
+
+// Computes the value of the `CB-ACCESS-SIGN` header: the base64 encoded
+// HMAC-SHA256 of timestamp + method + path + body, keyed with the
+// base64 decoded API secret
+pub fn sign(secret: &str, timestamp: &str, method: &str, path: &str, body: &str)
+    -> Result<String, Error> {
+
+    let key = STANDARD.decode(secret)?;
+    let what = format!("{}{}{}{}",
+                       timestamp,
+                       method.to_uppercase(),
+                       path,
+                       body);
+
+    let mut hmac = Hmac::new(Sha256::new(), &key);
+    hmac.input(what.as_bytes());
+
+    Ok(STANDARD.encode(hmac.result().code()))
+}
+
+// Generates `CB-ACCESS-TIMESTAMP` values in fractional seconds with
+// microsecond precision. If two requests land on the same microsecond
+// the later one is bumped forward so every timestamp is distinct.
+pub struct Timestamper {
+    last: Mutex<i64>
+}
+
+impl Timestamper {
+    pub fn new() -> Timestamper {
+        Timestamper {
+            last: Mutex::new(0)
+        }
+    }
+
+    pub fn now(&self) -> String {
+        let now = get_time();
+        let mut micros = now.sec * 1_000_000 + (now.nsec / 1_000) as i64;
+
+        let mut last = self.last.lock().unwrap();
+        if micros <= *last {
+            micros = *last + 1;
+        }
+        *last = micros;
+
+        format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+    }
+}
+
+// A throwaway key, the base64 encoding of "gdax-client test secret key 0123".
+// It has never been a real credential.
+#[cfg(feature = "test-util")]
+pub const EXAMPLE_SECRET: &'static str = "Z2RheC1jbGllbnQgdGVzdCBzZWNyZXQga2V5IDAxMjM=";
+
+// A request and the signature it must produce under EXAMPLE_SECRET, for
+// checking that an implementation of `sign` agrees with this one
+#[cfg(feature = "test-util")]
+#[derive(Clone, Copy, Debug)]
+pub struct SigningVector {
+    pub timestamp: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+    pub body: &'static str,
+    pub signature: &'static str
+}
+
+#[cfg(feature = "test-util")]
+impl SigningVector {
+    pub fn sign(&self) -> Result<String, Error> {
+        sign(EXAMPLE_SECRET, self.timestamp, self.method, self.path, self.body)
+    }
+
+    pub fn verify(&self) -> bool {
+        self.sign().map(|signature| signature == self.signature).unwrap_or(false)
+    }
+}
+
+#[cfg(feature = "test-util")]
+pub fn signing_vectors() -> Vec<SigningVector> {
+    vec![
+        SigningVector {
+            timestamp: "1465000000",
+            method: "GET",
+            path: "/accounts",
+            body: "",
+            signature: "SNXQi5Fj+IgGbCx7frsvZXjxAhE+chgMD85gLsSq3+Y="
+        },
+        SigningVector {
+            timestamp: "1465000000",
+            method: "POST",
+            path: "/orders",
+            body: "{\"type\":\"limit\"}",
+            signature: "syyj9Gx/QxzqSjs7qj7ysT1Tr+UkOOgWUvFABcgU8NE="
+        },
+        SigningVector {
+            timestamp: "1465000000.123456",
+            method: "DELETE",
+            path: "/orders/c5ab5eae-76be-480e-8961-00792dc7e138",
+            body: "",
+            signature: "54iMNMprEoDyZXo1PcKbgHm6yrUqV3+FhsiwbmkB8lM="
+        }
+    ]
+}
@@ -0,0 +1,62 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::public::{Candle, Stats, Tick};
+
+#[test]
+fn stats_with_blank_fields() {
+    let stats: Stats = serde_json::from_str(
+        r#"{"open":"","high":"NaN","low":null,"volume":""}"#).unwrap();
+
+    assert_eq!(stats.open, None);
+    assert_eq!(stats.high, None);
+    assert_eq!(stats.low, None);
+    assert_eq!(stats.volume, 0.);
+}
+
+#[test]
+fn stats_with_missing_fields() {
+    let stats: Stats = serde_json::from_str("{}").unwrap();
+
+    assert_eq!(stats.open, None);
+    assert_eq!(stats.volume, 0.);
+}
+
+#[test]
+fn stats_with_string_numbers() {
+    let stats: Stats = serde_json::from_str(
+        r#"{"open":"250.5","high":" 260 ","low":240.25,"volume":"1234.5"}"#).unwrap();
+
+    assert_eq!(stats.open, Some(250.5));
+    assert_eq!(stats.high, Some(260.));
+    assert_eq!(stats.low, Some(240.25));
+    assert_eq!(stats.volume, 1234.5);
+}
+
+#[test]
+fn stats_with_infinite_volume() {
+    let stats: Stats = serde_json::from_str(
+        r#"{"open":1,"high":2,"low":"-inf","volume":"inf"}"#).unwrap();
+
+    assert_eq!(stats.low, None);
+    assert_eq!(stats.volume, 0.);
+}
+
+#[test]
+fn tick_with_nan_volume() {
+    let tick: Tick = serde_json::from_str(
+        r#"{"trade_id":1,"price":250.5,"size":0.5,"bid":250.25,"ask":250.75,
+            "volume":"NaN","time":"2016-06-01T12:00:00.000Z"}"#).unwrap();
+
+    assert_eq!(tick.volume, 0.);
+    assert!(!tick.volume.is_nan());
+}
+
+#[test]
+fn candle_with_null_volume() {
+    let candles: Vec<Candle> = serde_json::from_str(
+        "[[1464782400,249.5,251.5,250,251,null]]").unwrap();
+
+    assert_eq!(candles[0].volume, 0.);
+    assert_eq!(candles[0].close, 251.);
+}
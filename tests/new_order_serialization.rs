@@ -0,0 +1,103 @@
+extern crate gdax_client;
+extern crate serde_json;
+extern crate uuid;
+
+use gdax_client::{Error, NewOrder, ProductId, Side, SizeOrFunds};
+use gdax_client::private::StopDirection;
+use uuid::Uuid;
+
+fn json(order: &NewOrder) -> String {
+    serde_json::to_string(order).unwrap()
+}
+
+#[test]
+fn limit_buy() {
+    let order = NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 250.25).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"limit","side":"buy","product_id":"BTC-USD","price":250.25,"size":1.5}"#);
+}
+
+#[test]
+fn limit_sell() {
+    let order = NewOrder::limit(Side::Sell, "ETH-BTC", 0.25, 0.0125).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"limit","side":"sell","product_id":"ETH-BTC","price":0.0125,"size":0.25}"#);
+}
+
+#[test]
+fn market_size() {
+    let order = NewOrder::market(Side::Sell, "BTC-USD", SizeOrFunds::Size(1.5)).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"market","side":"sell","product_id":"BTC-USD","size":1.5}"#);
+}
+
+#[test]
+fn market_funds() {
+    let order = NewOrder::market(Side::Buy, "BTC-USD", SizeOrFunds::Funds(100.5)).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"market","side":"buy","product_id":"BTC-USD","funds":100.5}"#);
+}
+
+#[test]
+fn stop_size() {
+    let order = NewOrder::stop(Side::Sell, "BTC-USD", SizeOrFunds::Size(1.5), 240.5).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"stop","side":"sell","product_id":"BTC-USD","stop":"loss","price":240.5,"size":1.5}"#);
+}
+
+#[test]
+fn stop_funds() {
+    let order = NewOrder::stop(Side::Buy, "BTC-USD", SizeOrFunds::Funds(100.5), 260.5).unwrap();
+    assert_eq!(json(&order),
+               r#"{"type":"stop","side":"buy","product_id":"BTC-USD","stop":"entry","price":260.5,"funds":100.5}"#);
+}
+
+#[test]
+fn stop_direction_can_be_overridden() {
+    let order = NewOrder::stop(Side::Buy, "BTC-USD", SizeOrFunds::Size(1.5), 240.5).unwrap()
+        .with_stop_direction(StopDirection::Loss);
+    assert_eq!(json(&order),
+               r#"{"type":"stop","side":"buy","product_id":"BTC-USD","stop":"loss","price":240.5,"size":1.5}"#);
+}
+
+#[test]
+fn unchecked_constructors_serialize_identically() {
+    assert_eq!(json(&NewOrder::limit_unchecked(Side::Buy, "BTC-USD", 1.5, 250.25)),
+               json(&NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 250.25).unwrap()));
+    assert_eq!(json(&NewOrder::market_unchecked(Side::Buy, "BTC-USD", SizeOrFunds::Funds(100.5))),
+               json(&NewOrder::market(Side::Buy, "BTC-USD", SizeOrFunds::Funds(100.5)).unwrap()));
+    assert_eq!(json(&NewOrder::stop_unchecked(Side::Sell, "BTC-USD", SizeOrFunds::Size(1.5), 240.5)),
+               json(&NewOrder::stop(Side::Sell, "BTC-USD", SizeOrFunds::Size(1.5), 240.5).unwrap()));
+}
+
+#[test]
+fn client_oid_is_included_when_set() {
+    let oid = Uuid::parse_str("c5ab5eae-76be-480e-8961-00792dc7e138").unwrap();
+    let order = NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 250.25).unwrap().with_client_oid(oid);
+
+    assert_eq!(order.client_oid(), Some(oid));
+    assert_eq!(json(&order),
+               r#"{"type":"limit","side":"buy","product_id":"BTC-USD","price":250.25,"size":1.5,"client_oid":"c5ab5eae-76be-480e-8961-00792dc7e138"}"#);
+}
+
+#[test]
+fn post_only_is_included_when_set() {
+    let order = NewOrder::limit(Side::Sell, "BTC-USD", 1.5, 250.25).unwrap().with_post_only();
+    assert_eq!(json(&order),
+               r#"{"type":"limit","side":"sell","product_id":"BTC-USD","price":250.25,"size":1.5,"post_only":true}"#);
+
+    let market = NewOrder::market(Side::Buy, "BTC-USD", SizeOrFunds::Funds(100.5)).unwrap();
+    assert_eq!(json(&market.clone().with_post_only()), json(&market));
+}
+
+#[test]
+fn constructors_take_a_product_id() {
+    let product_id = ProductId::new("BTC-USD").unwrap();
+    assert_eq!(json(&NewOrder::limit(Side::Buy, &product_id, 1.5, 250.25).unwrap()),
+               json(&NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 250.25).unwrap()));
+
+    match NewOrder::limit(Side::Buy, "btc_usd", 1.5, 250.25) {
+        Err(Error::InvalidProductId(id)) => assert_eq!(id, "btc_usd"),
+        other => panic!("expected an invalid product id, got {:?}", other)
+    }
+}
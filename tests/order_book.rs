@@ -0,0 +1,47 @@
+extern crate gdax_client;
+extern crate serde_json;
+extern crate uuid;
+
+use gdax_client::public::{BookEntry, FullBookEntry, OrderBook};
+use uuid::Uuid;
+
+#[test]
+fn aggregated_book_round_trips() {
+    let book = OrderBook {
+        sequence: 3,
+        bids: vec![BookEntry { price: 295.96, size: 4.39088265, num_orders: 2 }],
+        asks: vec![BookEntry { price: 295.97, size: 25.23542881, num_orders: 12 },
+                   BookEntry { price: 295.98, size: 0.5, num_orders: 1 }]
+    };
+
+    let json = serde_json::to_string(&book).unwrap();
+    let decoded: OrderBook<BookEntry> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(decoded.sequence, 3);
+    assert_eq!(decoded.bids.len(), 1);
+    assert_eq!(decoded.bids[0].price, 295.96);
+    assert_eq!(decoded.bids[0].size, 4.39088265);
+    assert_eq!(decoded.bids[0].num_orders, 2);
+    assert_eq!(decoded.asks.len(), 2);
+    assert_eq!(decoded.asks[1].price, 295.98);
+    assert_eq!(decoded.asks[1].num_orders, 1);
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+}
+
+#[test]
+fn full_book_round_trips() {
+    let order_id = Uuid::new_v4();
+    let book = OrderBook {
+        sequence: 12,
+        bids: vec![FullBookEntry { price: 295.96, size: 0.05088265, order_id: order_id }],
+        asks: vec![]
+    };
+
+    let json = serde_json::to_string(&book).unwrap();
+    let decoded: OrderBook<FullBookEntry> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(decoded.sequence, 12);
+    assert_eq!(decoded.bids[0].order_id, order_id);
+    assert_eq!(decoded.bids[0].size, 0.05088265);
+    assert!(decoded.asks.is_empty());
+}
@@ -0,0 +1,62 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::Side;
+use gdax_client::private::{OpenOrder, Order};
+
+// Roughly what `/orders/<id>` returns straight after `post_order`,
+// before the order has been matched against anything
+const JUST_PLACED: &'static str = r#"{
+    "id": "d0c5340b-6d6c-49d9-b567-48c4bfca13d2",
+    "price": 250.25,
+    "size": 1.5,
+    "product_id": "BTC-USD",
+    "side": "buy",
+    "status": "pending",
+    "created_at": "2016-06-01T12:00:00.000Z"
+}"#;
+
+#[test]
+fn just_placed_order() {
+    let order: Order = serde_json::from_str(JUST_PLACED).unwrap();
+
+    assert_eq!(order.status, "pending");
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.filled_size, 0.);
+    assert_eq!(order.executed_value, 0.);
+    assert_eq!(order.fill_fees, 0.);
+    assert!(!order.settled);
+    assert!(order.done_reason.is_none());
+    assert!(order.done_at.is_none());
+}
+
+#[test]
+fn just_placed_open_order() {
+    let order: OpenOrder = serde_json::from_str(JUST_PLACED).unwrap();
+
+    assert_eq!(order.price, 250.25);
+    assert_eq!(order.size, 1.5);
+    assert_eq!(order.filled_size, 0.);
+    assert_eq!(order.fill_fees, 0.);
+}
+
+#[test]
+fn partially_filled_order() {
+    let order: Order = serde_json::from_str(r#"{
+        "id": "d0c5340b-6d6c-49d9-b567-48c4bfca13d2",
+        "price": 250.25,
+        "size": 1.5,
+        "product_id": "BTC-USD",
+        "side": "sell",
+        "status": "open",
+        "settled": false,
+        "filled_size": 0.5,
+        "executed_value": 125.125,
+        "fill_fees": 0.375,
+        "created_at": "2016-06-01T12:00:00.000Z"
+    }"#).unwrap();
+
+    assert_eq!(order.filled_size, 0.5);
+    assert_eq!(order.executed_value, 125.125);
+    assert_eq!(order.fill_fees, 0.375);
+}
@@ -0,0 +1,104 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{Error, NewOrder, OrderError, Side, SizeOrFunds};
+use gdax_client::private::{ladder, Spacing};
+use gdax_client::public::Product;
+
+fn btc_usd() -> Product {
+    serde_json::from_str(r#"{
+        "id": "BTC-USD",
+        "base_currency": "BTC",
+        "quote_currency": "USD",
+        "base_min_size": 0.01,
+        "base_max_size": 10000,
+        "base_increment": 0.00000001,
+        "quote_increment": 0.01,
+        "status": "online"
+    }"#).unwrap()
+}
+
+fn order_error(order: &NewOrder) -> OrderError {
+    match order.validate(&btc_usd()) {
+        Err(Error::InvalidOrder(err)) => err,
+        other => panic!("expected an invalid order, got {:?}", other)
+    }
+}
+
+#[test]
+fn valid_limit_order_passes() {
+    let order = NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 250.25).unwrap();
+    assert!(order.validate(&btc_usd()).is_ok());
+}
+
+#[test]
+fn nan_size_is_rejected() {
+    let order = NewOrder::limit_unchecked(Side::Buy, "BTC-USD", std::f64::NAN, 250.25);
+    match order_error(&order) {
+        OrderError::NotFinite { field, .. } => assert_eq!(field, "size"),
+        err => panic!("unexpected error {:?}", err)
+    }
+}
+
+#[test]
+fn nan_price_is_rejected() {
+    let order = NewOrder::limit_unchecked(Side::Buy, "BTC-USD", 1.5, std::f64::NAN);
+    match order_error(&order) {
+        OrderError::NotFinite { field, .. } => assert_eq!(field, "price"),
+        err => panic!("unexpected error {:?}", err)
+    }
+}
+
+#[test]
+fn infinite_funds_are_rejected() {
+    let order = NewOrder::market_unchecked(Side::Buy, "BTC-USD", SizeOrFunds::Funds(std::f64::INFINITY));
+    match order_error(&order) {
+        OrderError::NotFinite { field, .. } => assert_eq!(field, "funds"),
+        err => panic!("unexpected error {:?}", err)
+    }
+}
+
+#[test]
+fn zero_price_is_rejected() {
+    let order = NewOrder::limit_unchecked(Side::Buy, "BTC-USD", 1.5, 0.);
+    assert_eq!(order_error(&order), OrderError::NotPositive { field: "price", got: 0. });
+}
+
+#[test]
+fn negative_price_is_rejected() {
+    let order = NewOrder::stop_unchecked(Side::Sell, "BTC-USD", SizeOrFunds::Size(1.5), -240.5);
+    assert_eq!(order_error(&order), OrderError::NotPositive { field: "price", got: -240.5 });
+}
+
+fn ladder_error(price_low: f64, price_high: f64, spacing: Spacing) -> String {
+    match ladder(Side::Buy, &btc_usd(), 1., price_low, price_high, 4, spacing) {
+        Err(Error::InvalidArgument(message)) => message,
+        other => panic!("expected an invalid argument, got {:?}", other)
+    }
+}
+
+#[test]
+fn ladder_spreads_over_the_range() {
+    let orders = ladder(Side::Buy, &btc_usd(), 1., 240., 270., 4, Spacing::Linear).unwrap();
+    let prices = orders.iter().map(|order| match *order {
+        NewOrder::Limit { price, .. } => price,
+        _ => panic!("ladder should only build limit orders")
+    }).collect::<Vec<_>>();
+    assert_eq!(prices, vec![270., 260., 250., 240.]);
+}
+
+#[test]
+fn ladder_rejects_geometric_from_zero() {
+    assert!(ladder_error(0., 270., Spacing::Geometric).contains("positive"));
+}
+
+#[test]
+fn ladder_rejects_inverted_range() {
+    assert!(ladder_error(270., 240., Spacing::Linear).contains("above"));
+}
+
+#[test]
+fn ladder_rejects_non_finite_range() {
+    assert!(ladder_error(std::f64::NAN, 270., Spacing::Linear).contains("not finite"));
+    assert!(ladder_error(240., std::f64::INFINITY, Spacing::Geometric).contains("not finite"));
+}
@@ -0,0 +1,95 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::Side;
+use gdax_client::pnl::{AccountingMethod, PnlCalculator};
+use gdax_client::private::Fill;
+
+fn fill(side: Side, size: f64, price: f64, fee: f64) -> Fill {
+    let side = match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell"
+    };
+    serde_json::from_str(&format!(r#"{{
+        "trade_id": 74,
+        "product_id": "BTC-USD",
+        "price": {},
+        "size": {},
+        "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+        "created_at": "2016-06-01T12:00:00.000Z",
+        "liquidity": "T",
+        "fee": {},
+        "settled": true,
+        "side": "{}"
+    }}"#, price, size, fee, side)).unwrap()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn fifo_partial_close_uses_oldest_lot_first() {
+    let mut pnl = PnlCalculator::new(AccountingMethod::Fifo);
+    pnl.add(&fill(Side::Buy, 1., 100., 0.));
+    pnl.add(&fill(Side::Buy, 1., 110., 0.));
+    pnl.add(&fill(Side::Sell, 1.5, 120., 0.));
+
+    // 1 @ 100 and 0.5 @ 110 closed at 120
+    assert_close(pnl.realized(), 25.);
+    assert_close(pnl.position(), 0.5);
+    assert_close(pnl.average_entry().unwrap(), 110.);
+    assert_close(pnl.average_exit().unwrap(), 120.);
+}
+
+#[test]
+fn average_cost_partial_close_uses_pooled_price() {
+    let mut pnl = PnlCalculator::new(AccountingMethod::AverageCost);
+    pnl.add(&fill(Side::Buy, 1., 100., 0.));
+    pnl.add(&fill(Side::Buy, 1., 110., 0.));
+    pnl.add(&fill(Side::Sell, 1.5, 120., 0.));
+
+    // 1.5 closed at 120 against an average of 105
+    assert_close(pnl.realized(), 22.5);
+    assert_close(pnl.position(), 0.5);
+    assert_close(pnl.average_entry().unwrap(), 105.);
+}
+
+#[test]
+fn flipping_through_zero_closes_before_opening() {
+    for &method in &[AccountingMethod::Fifo, AccountingMethod::AverageCost] {
+        let mut pnl = PnlCalculator::new(method);
+        pnl.add(&fill(Side::Buy, 1., 100., 0.));
+        pnl.add(&fill(Side::Sell, 3., 90., 0.));
+
+        // The long closes at a loss of 10 and a short of 2 opens at 90
+        assert_close(pnl.realized(), -10.);
+        assert_close(pnl.position(), -2.);
+        assert_close(pnl.average_entry().unwrap(), 90.);
+
+        pnl.add(&fill(Side::Buy, 2., 80., 0.));
+
+        assert_close(pnl.realized(), 10.);
+        assert_close(pnl.position(), 0.);
+        assert!(pnl.average_entry().is_none());
+        assert_close(pnl.average_exit().unwrap(), 250. / 3.);
+    }
+}
+
+#[test]
+fn fees_are_taken_off_realized() {
+    let mut pnl = PnlCalculator::new(AccountingMethod::Fifo);
+    pnl.add(&fill(Side::Buy, 1., 100., 0.25));
+
+    // Opening alone realizes nothing but the fee
+    assert_close(pnl.realized(), -0.25);
+
+    pnl.add(&fill(Side::Sell, 1., 110., 0.275));
+
+    assert_close(pnl.fees(), 0.525);
+    assert_close(pnl.realized(), 9.475);
+
+    pnl.reset();
+    assert_close(pnl.realized(), 0.);
+    assert_close(pnl.fees(), 0.);
+}
@@ -0,0 +1,38 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::public::{Product, ProductStatus};
+
+fn product(status: &str) -> Product {
+    serde_json::from_str(&format!(r#"{{
+        "id": "BTC-USD",
+        "base_currency": "BTC",
+        "quote_currency": "USD",
+        "base_min_size": 0.01,
+        "base_max_size": 10000,
+        "base_increment": 0.00000001,
+        "quote_increment": 0.01
+        {}
+    }}"#, status)).unwrap()
+}
+
+#[test]
+fn online_product_can_trade() {
+    let product = product(r#", "status": "online""#);
+    assert_eq!(product.status, ProductStatus::Online);
+    assert!(product.can_trade());
+}
+
+#[test]
+fn unknown_status_is_kept_but_not_tradable() {
+    let product = product(r#", "status": "auction""#);
+    assert_eq!(product.status, ProductStatus::Other("auction".to_string()));
+    assert_eq!(product.status.as_str(), "auction");
+    assert!(!product.can_trade());
+}
+
+#[test]
+fn missing_status_is_not_tradable() {
+    let product = product("");
+    assert!(!product.can_trade());
+}
@@ -0,0 +1,38 @@
+extern crate chrono;
+extern crate gdax_client;
+extern crate serde_json;
+extern crate uuid;
+
+use chrono::{TimeZone, UTC};
+use gdax_client::private::{report_date, NewReport, ReportFormat};
+use uuid::Uuid;
+
+#[test]
+fn report_dates_have_milliseconds_and_zulu() {
+    assert_eq!(report_date(&UTC.ymd(2016, 6, 1).and_hms(0, 0, 0)),
+               "2016-06-01T00:00:00.000Z");
+    assert_eq!(report_date(&UTC.ymd(2016, 12, 31).and_hms_milli(23, 59, 59, 7)),
+               "2016-12-31T23:59:59.007Z");
+}
+
+#[test]
+fn fills_report_body() {
+    let report = NewReport::fills("BTC-USD",
+                                  UTC.ymd(2016, 6, 1).and_hms(0, 0, 0),
+                                  UTC.ymd(2016, 6, 2).and_hms_milli(12, 30, 0, 250));
+
+    assert_eq!(serde_json::to_string(&report).unwrap(),
+               r#"{"type":"fills","start_date":"2016-06-01T00:00:00.000Z","end_date":"2016-06-02T12:30:00.250Z","product_id":"BTC-USD","format":"pdf"}"#);
+}
+
+#[test]
+fn account_report_body() {
+    let account_id = Uuid::parse_str("c5ab5eae-76be-480e-8961-00792dc7e138").unwrap();
+    let report = NewReport::account(account_id,
+                                    UTC.ymd(2016, 6, 1).and_hms(0, 0, 0),
+                                    UTC.ymd(2016, 7, 1).and_hms(0, 0, 0))
+        .with_format(ReportFormat::Csv);
+
+    assert_eq!(serde_json::to_string(&report).unwrap(),
+               r#"{"type":"account","start_date":"2016-06-01T00:00:00.000Z","end_date":"2016-07-01T00:00:00.000Z","account_id":"c5ab5eae-76be-480e-8961-00792dc7e138","format":"csv"}"#);
+}
@@ -0,0 +1,56 @@
+extern crate gdax_client;
+
+use gdax_client::Error;
+use gdax_client::test_util::{sign, signing_vectors, Timestamper, EXAMPLE_SECRET as SECRET};
+
+#[test]
+fn signs_request_without_body() {
+    assert_eq!(sign(SECRET, "1465000000", "GET", "/accounts", "").unwrap(),
+               "SNXQi5Fj+IgGbCx7frsvZXjxAhE+chgMD85gLsSq3+Y=");
+}
+
+#[test]
+fn signs_request_with_body() {
+    assert_eq!(sign(SECRET, "1465000000", "POST", "/orders", "{\"type\":\"limit\"}").unwrap(),
+               "syyj9Gx/QxzqSjs7qj7ysT1Tr+UkOOgWUvFABcgU8NE=");
+}
+
+#[test]
+fn example_vectors_verify() {
+    for vector in signing_vectors() {
+        assert_eq!(sign(SECRET, vector.timestamp, vector.method, vector.path, vector.body).unwrap(),
+                   vector.signature);
+        assert!(vector.verify());
+    }
+}
+
+#[test]
+fn tampered_vector_fails() {
+    let mut vector = signing_vectors()[1];
+    vector.body = "{\"type\":\"market\"}";
+    assert!(!vector.verify());
+}
+
+#[test]
+fn uppercases_method() {
+    assert_eq!(sign(SECRET, "1465000000", "get", "/accounts", "").unwrap(),
+               sign(SECRET, "1465000000", "GET", "/accounts", "").unwrap());
+}
+
+#[test]
+fn rejects_invalid_secret() {
+    match sign("not a base64 secret!", "1465000000", "GET", "/accounts", "") {
+        Err(Error::InvalidSecretKey) => {}
+        other => panic!("expected InvalidSecretKey, got {:?}", other)
+    }
+}
+
+#[test]
+fn rapid_timestamps_are_distinct() {
+    let timestamper = Timestamper::new();
+    let first = timestamper.now();
+    let second = timestamper.now();
+
+    assert!(first != second);
+    assert!(second.parse::<f64>().unwrap() > first.parse::<f64>().unwrap());
+}